@@ -0,0 +1,41 @@
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let git_commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| String::from("unknown"));
+
+    let target = std::env::var("TARGET").unwrap_or_else(|_| String::from("unknown"));
+    let hackrfone_version = locked_version("hackrfone").unwrap_or_else(|| String::from("unknown"));
+
+    println!("cargo:rustc-env=ZWAVE_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=ZWAVE_TARGET={}", target);
+    println!("cargo:rustc-env=ZWAVE_HACKRFONE_VERSION={}", hackrfone_version);
+}
+
+// Read the resolved version of a dependency from Cargo.lock
+fn locked_version(package: &str) -> Option<String> {
+    let lock = std::fs::read_to_string("Cargo.lock").ok()?;
+    let name_line = format!("name = \"{}\"", package);
+    let mut lines = lock.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim() == name_line {
+            let version_line = lines.next()?;
+            let version = version_line.trim().strip_prefix("version = ")?;
+            return Some(version.trim_matches('"').to_string());
+        }
+    }
+
+    None
+}
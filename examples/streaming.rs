@@ -0,0 +1,39 @@
+// Analyze raw HackRF samples from stdin in one-second chunks, e.g.
+//
+//   hackrf_transfer -r - -f 868400000 -s 10000000 | cargo run --example streaming -- 10000000
+
+use std::io::Read;
+use zwave_module::detector::DetectorSettings;
+use zwave_module::streaming::StreamingAnalyzer;
+
+const DETECTION_THRESHOLD: f64 = 50.0;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let sample_rate: u64 = std::env::args().nth(1).ok_or("Usage: streaming <sample rate>")?.parse()?;
+    // One second of bytes, two (I and Q) per radio sample
+    let chunk = sample_rate * 2;
+
+    let settings = DetectorSettings { detection_threshold: DETECTION_THRESHOLD, ..Default::default() };
+    let mut analyzer = StreamingAnalyzer::new(chunk, settings, |result| {
+        println!(
+            "{:>8.1} s  max {:5.1} dB  noise {:5.1} dB{}",
+            result.start_sample as f64 / chunk as f64,
+            result.max_strength_db.unwrap_or(0.0),
+            result.noise_floor_db.unwrap_or(0.0),
+            if result.detected { "  detected" } else { "" },
+        );
+    });
+
+    let mut stdin = std::io::stdin().lock();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = stdin.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        analyzer.push(&buffer[..read]);
+    }
+    analyzer.flush();
+
+    Ok(())
+}
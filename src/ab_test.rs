@@ -0,0 +1,169 @@
+use crate::{analyze_samples, budget, byte_histogram, capture, check_amp_with_lna, encryption, estimate_noise_floor, resolve_gain, scan_id, shutdown, Config, Gains, RadioSlot};
+use crate::{LNA_GAIN_MAX, LNA_GAIN_STEP, VGA_GAIN_MAX, VGA_GAIN_STEP};
+use serde::{Serialize, Deserialize};
+use std::time::{Duration, Instant};
+
+// A |t| above this is reported as a significant difference between the arms
+const SIGNIFICANCE_T: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AbTestConfig {
+    pub a: ParameterSet,
+    pub b: ParameterSet,
+}
+
+// Differences from the base config for one arm of an A/B test, unset fields use the base value
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ParameterSet {
+    pub label: String,
+    #[serde(default)]
+    pub frequency: Option<u64>,
+    #[serde(default)]
+    pub lna_gain: Option<i32>,
+    #[serde(default)]
+    pub vga_gain: Option<i32>,
+    #[serde(default)]
+    pub detection_threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ArmResult {
+    label: String,
+    frequency: u64,
+    lna_gain: u16,
+    vga_gain: u16,
+    detection_threshold: f64,
+    chunks: usize,
+    detection_seconds: u64,
+    mean_strength: f64,
+    mean_snr: f64,
+    snr_std_dev: f64,
+}
+
+#[derive(Serialize)]
+struct AbTestResult {
+    a: ArmResult,
+    b: ArmResult,
+    // Welch's t-statistic on the per-chunk SNR of A minus B
+    snr_t_statistic: f64,
+    significant: bool,
+}
+
+struct Arm {
+    frequency: u64,
+    gains: Gains,
+    threshold: f64,
+    strengths: Vec<f64>,
+    snrs: Vec<f64>,
+    detections: u64,
+}
+
+impl Arm {
+    fn new(config: &Config, set: &ParameterSet) -> Result<Self, Box<dyn std::error::Error>> {
+        let lna = resolve_gain("LNA", set.lna_gain.unwrap_or(config.lna_gain), LNA_GAIN_MAX, LNA_GAIN_STEP, config.gain_clamp)?;
+        let vga = resolve_gain("VGA", set.vga_gain.unwrap_or(config.vga_gain), VGA_GAIN_MAX, VGA_GAIN_STEP, config.gain_clamp)?;
+        check_amp_with_lna(config, lna)?;
+        Ok(Arm {
+            frequency: set.frequency.unwrap_or(config.frequency),
+            gains: Gains { lna, vga, amp: config.amp_enable, antenna_power: config.antenna_power }.receive_safe(config),
+            threshold: set.detection_threshold.unwrap_or(config.detection_threshold),
+            strengths: Vec::new(),
+            snrs: Vec::new(),
+            detections: 0,
+        })
+    }
+
+    fn result(&self, label: &str) -> ArmResult {
+        let (mean_strength, _) = mean_and_variance(&self.strengths);
+        let (mean_snr, snr_variance) = mean_and_variance(&self.snrs);
+        ArmResult {
+            label: label.to_string(),
+            frequency: self.frequency,
+            lna_gain: self.gains.lna,
+            vga_gain: self.gains.vga,
+            detection_threshold: self.threshold,
+            chunks: self.snrs.len(),
+            detection_seconds: self.detections,
+            mean_strength,
+            mean_snr,
+            snr_std_dev: snr_variance.sqrt(),
+        }
+    }
+}
+
+fn mean_and_variance(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    (mean, variance)
+}
+
+fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, variance_a) = mean_and_variance(a);
+    let (mean_b, variance_b) = mean_and_variance(b);
+    let standard_error = (variance_a / a.len().max(1) as f64 + variance_b / b.len().max(1) as f64).sqrt();
+    if standard_error > 0.0 {
+        (mean_a - mean_b) / standard_error
+    } else {
+        0.0
+    }
+}
+
+/// Alternate one-second chunks between parameter sets A and B for the scan duration,
+/// so slow changes in the RF environment affect both arms equally.
+pub async fn run_ab_test(config: &Config, radio: &mut RadioSlot) -> Result<(), Box<dyn std::error::Error>> {
+    let ab_test = config.ab_test.as_ref().ok_or("ab-test mode needs an ab_test section in the config")?;
+    let mut arms = [Arm::new(config, &ab_test.a)?, Arm::new(config, &ab_test.b)?];
+    scan_id::start(config.scan_uuid);
+
+    scan_println!("Running A/B test \"{}\" vs \"{}\" for {} seconds...", ab_test.a.label, ab_test.b.label, config.scan_duration);
+
+    let scan_start_time = Instant::now();
+    let scan_duration = Duration::from_secs(config.scan_duration);
+    let mut chunk = 0usize;
+
+    while scan_start_time.elapsed() + Duration::from_secs(1) <= scan_duration && !shutdown::requested() && !budget::spent() {
+        let arm = &mut arms[chunk % 2];
+        chunk += 1;
+
+        let Some(raw_samples) = capture(config, radio, arm.frequency, config.sample_rate, arm.gains, Duration::from_secs(1))? else {
+            continue;
+        };
+        let noise_floor = estimate_noise_floor(&byte_histogram(&raw_samples)).unwrap_or(0.0);
+        let strength = analyze_samples(raw_samples).into_iter().fold(0.0_f64, f64::max);
+
+        arm.strengths.push(strength);
+        arm.snrs.push(strength - noise_floor);
+        if strength > arm.threshold {
+            arm.detections += 1;
+        }
+    }
+
+    let t_statistic = welch_t(&arms[0].snrs, &arms[1].snrs);
+    let result = AbTestResult {
+        a: arms[0].result(&ab_test.a.label),
+        b: arms[1].result(&ab_test.b.label),
+        snr_t_statistic: t_statistic,
+        significant: t_statistic.abs() > SIGNIFICANCE_T,
+    };
+
+    scan_println!("{:<12} {:>8} {:>10} {:>10} {:>10}", "", "chunks", "detect s", "mean SNR", "SNR std");
+    for arm in [&result.a, &result.b] {
+        scan_println!("{:<12} {:>8} {:>10} {:>10.1} {:>10.1}", arm.label, arm.chunks, arm.detection_seconds, arm.mean_snr, arm.snr_std_dev);
+    }
+    scan_println!(
+        "SNR difference is {} (t = {:.2})",
+        if result.significant { "significant" } else { "not significant" },
+        t_statistic,
+    );
+
+    let json = serde_json::to_string_pretty(&result)?;
+    encryption::write_artifact(config.encryption.as_ref(), &scan_id::artifact_path("zwave_abtest.json"), json.as_bytes())?;
+
+    Ok(())
+}
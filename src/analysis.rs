@@ -0,0 +1,85 @@
+//! Per-sample strength and noise-floor estimates used by the detector.
+//!
+//! Each raw byte from the HackRF is treated as one sample and converted to a strength of
+//! `20 * log10(byte)` dB, so a full-scale byte of 255 is about 48.1 dB.
+
+/// Strength of one raw sample in dB, 0 for a zero sample.
+pub fn sample_db(sample: u8) -> f64 {
+    if sample > 0 {
+        20.0 * (sample as f64).log10()
+    } else {
+        0.0
+    }
+}
+
+pub fn analyze_samples(samples: Vec<u8>) -> Vec<f64> {
+    samples.iter().map(|&sample| sample_db(sample)).collect()
+}
+
+/// Cheap power estimate from every `decimation`th sample, in the same dB scale as [`sample_db`].
+pub fn coarse_power_db(samples: &[u8], decimation: usize) -> f64 {
+    let (sum, count) = samples.iter().step_by(decimation.max(1))
+        .fold((0.0, 0usize), |(sum, count), &sample| (sum + (sample as f64).powi(2), count + 1));
+    let mean_power = sum / count.max(1) as f64;
+    if mean_power > 0.0 {
+        10.0 * mean_power.log10()
+    } else {
+        0.0
+    }
+}
+
+pub fn byte_histogram(samples: &[u8]) -> [u64; 256] {
+    let mut histogram = [0u64; 256];
+    for &sample in samples {
+        histogram[sample as usize] += 1;
+    }
+    histogram
+}
+
+/// Median sample strength in dB, None for an empty histogram.
+pub fn estimate_noise_floor(histogram: &[u64; 256]) -> Option<f64> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut seen = 0u64;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen * 2 >= total {
+            return Some(sample_db(value as u8));
+        }
+    }
+
+    None
+}
+
+/// Confidence interval on [`estimate_noise_floor`], in dB, at `z` standard errors (1.96 for 95%).
+///
+/// The median's interval is distribution-free: the number of samples below the true median is
+/// Binomial(n, 0.5), so with the normal approximation the interval runs from the sample ranked
+/// `n/2 - z*sqrt(n)/2` to the one ranked `n/2 + z*sqrt(n)/2`. Short captures give a wide
+/// interval; long ones usually narrow it to the single byte value of the median.
+pub fn noise_floor_interval(histogram: &[u64; 256], z: f64) -> Option<(f64, f64)> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let half_width = z * (total as f64).sqrt() / 2.0;
+    let low_rank = (total as f64 / 2.0 - half_width).floor().max(1.0) as u64;
+    let high_rank = (total as f64 / 2.0 + half_width).ceil().min(total as f64) as u64;
+    Some((sample_db(value_at_rank(histogram, low_rank)), sample_db(value_at_rank(histogram, high_rank))))
+}
+
+// Value of the sample at 1-based `rank` in sorted order
+fn value_at_rank(histogram: &[u64; 256], rank: u64) -> u8 {
+    let mut seen = 0u64;
+    for (value, &count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return value as u8;
+        }
+    }
+    u8::MAX
+}
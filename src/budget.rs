@@ -0,0 +1,49 @@
+// Lifetime cap on captured bytes for a session. Every successful capture counts towards the total,
+// and loops that run several scans check the budget before each one, so a run never starts a scan
+// once it is spent. The limit comes from the first config and covers the whole session.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+static CAPTURED: AtomicU64 = AtomicU64::new(0);
+static LIMIT: Mutex<Option<u64>> = Mutex::new(None);
+
+pub fn set_limit(limit: Option<u64>) {
+    *LIMIT.lock().unwrap() = limit;
+}
+
+pub fn limit() -> Option<u64> {
+    *LIMIT.lock().unwrap()
+}
+
+pub fn record(bytes: usize) {
+    CAPTURED.fetch_add(bytes as u64, Ordering::SeqCst);
+}
+
+pub fn captured() -> u64 {
+    CAPTURED.load(Ordering::SeqCst)
+}
+
+/// Bytes left in `budget`, zero once it is spent.
+pub fn remaining(budget: u64) -> u64 {
+    budget.saturating_sub(captured())
+}
+
+/// Whether the session budget is spent, without logging.
+pub fn exhausted() -> bool {
+    limit().is_some_and(|budget| remaining(budget) == 0)
+}
+
+/// Check the session budget before the next scan, logging what is left. True once it is spent.
+pub fn spent() -> bool {
+    let Some(budget) = limit() else {
+        return false;
+    };
+    let spent = exhausted();
+    if spent {
+        scan_println!("Scan budget of {} bytes spent, stopping", budget);
+    } else {
+        scan_println!("Scan budget: {} of {} bytes remaining", remaining(budget), budget);
+    }
+    spent
+}
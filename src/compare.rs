@@ -0,0 +1,216 @@
+use crate::{analyze_samples, budget, byte_histogram, capture, encryption, estimate_noise_floor, history, scan_id, shutdown, Config, Gains, RadioSlot};
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+
+// Skip a channel that is active for active_rounds rounds in a row, likely a fixed interferer,
+// for the next cooloff_rounds rounds so the time goes to the other channels
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterfererSkipConfig {
+    #[serde(default = "default_active_rounds")]
+    pub active_rounds: u64,
+    #[serde(default = "default_cooloff_rounds")]
+    pub cooloff_rounds: u64,
+}
+
+fn default_active_rounds() -> u64 {
+    3
+}
+
+fn default_cooloff_rounds() -> u64 {
+    5
+}
+
+#[derive(Serialize)]
+pub struct ChannelResult {
+    pub frequency: u64,
+    pub noise_floor_db: f64,
+    pub duty_cycle: f64,
+    pub max_strength: f64,
+    pub windows: usize,
+    pub recommended: bool,
+    // Rounds skipped as a persistent interferer, with interferer_skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_rounds: Option<u64>,
+    // Differences from the reference channel, when reference_frequency is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<Delta>,
+}
+
+#[derive(Serialize)]
+pub struct Delta {
+    pub strength: f64,
+    pub noise_floor_db: f64,
+    pub snr: f64,
+}
+
+#[derive(Serialize)]
+pub struct CompareResult {
+    pub recommended_frequency: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_frequency: Option<u64>,
+    // Quietest first: lowest noise floor, then lowest duty cycle
+    pub channels: Vec<ChannelResult>,
+}
+
+struct Channel {
+    frequency: u64,
+    histogram: [u64; 256],
+    max_strength: f64,
+    windows: usize,
+    active_windows: usize,
+    consecutive_active: u64,
+    skip_until_round: u64,
+    skipped_rounds: u64,
+}
+
+pub async fn run_compare(config: &Config, gains: Gains, radio: &mut RadioSlot) -> Result<(), Box<dyn std::error::Error>> {
+    if config.compare_frequencies.len() < 2 {
+        return Err("compare mode needs at least two compare_frequencies".into());
+    }
+    if let Some(reference) = config.reference_frequency {
+        if !config.compare_frequencies.contains(&reference) {
+            return Err(format!("reference_frequency {} is not one of compare_frequencies", reference).into());
+        }
+    }
+
+    scan_id::start(config.scan_uuid);
+    let result = compare_frequencies(config, gains, radio, &config.compare_frequencies, config.compare_rounds)?;
+
+    let json = serde_json::to_string_pretty(&result)?;
+    encryption::write_artifact(config.encryption.as_ref(), &scan_id::artifact_path("zwave_compare.json"), json.as_bytes())?;
+
+    Ok(())
+}
+
+/// Scan each frequency in turn, one-second window at a time for `rounds` rounds,
+/// then rank the channels and recommend the quietest one.
+pub fn compare_frequencies(config: &Config, gains: Gains, radio: &mut RadioSlot, frequencies: &[u64], rounds: u64) -> Result<CompareResult, Box<dyn std::error::Error>> {
+    let mut channels: Vec<Channel> = frequencies.iter()
+        .map(|&frequency| Channel {
+            frequency,
+            histogram: [0; 256],
+            max_strength: 0.0,
+            windows: 0,
+            active_windows: 0,
+            consecutive_active: 0,
+            skip_until_round: 0,
+            skipped_rounds: 0,
+        })
+        .collect();
+
+    scan_println!("Comparing {} frequencies over {} rounds...", channels.len(), rounds);
+
+    // Interleave the channels so changing conditions affect them all alike
+    for round in 1..=rounds {
+        if shutdown::requested() || budget::spent() {
+            break;
+        }
+        scan_println!("Round {} of {}", round, rounds);
+
+        for channel in channels.iter_mut() {
+            if round < channel.skip_until_round {
+                channel.skipped_rounds += 1;
+                continue;
+            }
+            let Some(raw_samples) = capture(config, radio, channel.frequency, config.sample_rate, gains, Duration::from_secs(1))? else {
+                continue;
+            };
+            for (total, count) in channel.histogram.iter_mut().zip(byte_histogram(&raw_samples)) {
+                *total += count;
+            }
+            let strength = analyze_samples(raw_samples).into_iter().fold(0.0_f64, f64::max);
+
+            channel.windows += 1;
+            channel.max_strength = channel.max_strength.max(strength);
+            if strength > config.detection_threshold {
+                channel.active_windows += 1;
+                channel.consecutive_active += 1;
+            } else {
+                channel.consecutive_active = 0;
+            }
+
+            if let Some(skip) = config.interferer_skip.as_ref().filter(|skip| channel.consecutive_active >= skip.active_rounds.max(1)) {
+                scan_println!(
+                    "{:.3} MHz active for {} rounds in a row, skipping it for {} rounds as a likely interferer",
+                    channel.frequency as f64 / 1_000_000.0,
+                    channel.consecutive_active,
+                    skip.cooloff_rounds,
+                );
+                channel.skip_until_round = round + 1 + skip.cooloff_rounds;
+                channel.consecutive_active = 0;
+            }
+        }
+    }
+
+    let mut ranked: Vec<ChannelResult> = channels.iter()
+        .filter(|channel| channel.windows > 0)
+        .map(|channel| ChannelResult {
+            frequency: channel.frequency,
+            noise_floor_db: estimate_noise_floor(&channel.histogram).unwrap_or(0.0),
+            duty_cycle: channel.active_windows as f64 / channel.windows as f64,
+            max_strength: channel.max_strength,
+            windows: channel.windows,
+            recommended: false,
+            skipped_rounds: config.interferer_skip.as_ref().map(|_| channel.skipped_rounds),
+            delta: None,
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        a.noise_floor_db.total_cmp(&b.noise_floor_db)
+            .then(a.duty_cycle.total_cmp(&b.duty_cycle))
+    });
+    if let Some(quietest) = ranked.first_mut() {
+        quietest.recommended = true;
+    }
+
+    if let Some(history) = config.noise_history.as_ref() {
+        for channel in &ranked {
+            history::record(history, config.encryption.as_ref(), channel.frequency, channel.noise_floor_db)?;
+        }
+    }
+
+    // Only a reference that was actually captured has anything to compare against
+    let reference = config.reference_frequency
+        .and_then(|frequency| ranked.iter().find(|channel| channel.frequency == frequency))
+        .map(|channel| (channel.frequency, channel.max_strength, channel.noise_floor_db));
+    if let Some((_, strength, noise_floor)) = reference {
+        for channel in ranked.iter_mut() {
+            channel.delta = Some(Delta {
+                strength: channel.max_strength - strength,
+                noise_floor_db: channel.noise_floor_db - noise_floor,
+                snr: (channel.max_strength - channel.noise_floor_db) - (strength - noise_floor),
+            });
+        }
+    }
+
+    let result = CompareResult {
+        recommended_frequency: ranked.first().map(|channel| channel.frequency),
+        reference_frequency: reference.map(|(frequency, _, _)| frequency),
+        channels: ranked,
+    };
+
+    scan_println!("{:>14} {:>12} {:>11} {:>13}", "frequency MHz", "noise dB", "duty cycle", "max strength");
+    for channel in &result.channels {
+        let delta = channel.delta.as_ref()
+            .filter(|_| Some(channel.frequency) != result.reference_frequency)
+            .map(|delta| format!("  (Δstrength {:+.1}, Δnoise {:+.1}, ΔSNR {:+.1})", delta.strength, delta.noise_floor_db, delta.snr))
+            .unwrap_or_default();
+        let skipped = channel.skipped_rounds
+            .filter(|&rounds| rounds > 0)
+            .map(|rounds| format!("  (skipped {} rounds as an interferer)", rounds))
+            .unwrap_or_default();
+        scan_println!(
+            "{:>14.3} {:>12.1} {:>10.0}% {:>13.1}{}{}{}{}",
+            channel.frequency as f64 / 1_000_000.0,
+            channel.noise_floor_db,
+            channel.duty_cycle * 100.0,
+            channel.max_strength,
+            delta,
+            skipped,
+            if Some(channel.frequency) == result.reference_frequency { "  <- reference" } else { "" },
+            if channel.recommended { "  <- recommended" } else { "" },
+        );
+    }
+
+    Ok(result)
+}
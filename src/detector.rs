@@ -0,0 +1,94 @@
+//! Per-window detection, shared by the CLI's scans and [`crate::streaming`].
+//!
+//! Each window optionally goes through a cheap energy gate first; windows below it are marked idle
+//! without further analysis. The rest are reduced to their peak strength, which is a detection
+//! when it is above the threshold or, with rise detection, far enough above a sliding baseline.
+
+use crate::analysis::{coarse_power_db, sample_db};
+use serde::{Serialize, Deserialize};
+
+/// Detect on a rise above a sliding baseline instead of a fixed threshold.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RiseDetection {
+    /// How far a window's strength must rise above the baseline to count as a detection.
+    #[serde(default = "default_rise_delta_db")]
+    pub delta_db: f64,
+    /// Weight of each new window in the exponential moving average baseline.
+    #[serde(default = "default_baseline_alpha")]
+    pub baseline_alpha: f64,
+}
+
+fn default_rise_delta_db() -> f64 {
+    6.0
+}
+
+fn default_baseline_alpha() -> f64 {
+    0.1
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetectorSettings {
+    pub detection_threshold: f64,
+    pub rise_detection: Option<RiseDetection>,
+    /// Windows whose coarse power is below this many dB are idle.
+    pub energy_gate_db: Option<f64>,
+    /// Only every Nth sample goes into the coarse power estimate.
+    pub energy_gate_decimation: usize,
+}
+
+/// Outcome of one window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowDetection {
+    /// Whether the energy gate marked the window idle, skipping the rest of the analysis.
+    pub idle: bool,
+    /// Peak strength in dB, None for an idle or empty window.
+    pub strength: Option<f64>,
+    pub detected: bool,
+}
+
+pub struct Detector {
+    settings: DetectorSettings,
+    baseline: Option<f64>,
+    max_rise: f64,
+}
+
+impl Detector {
+    pub fn new(settings: DetectorSettings) -> Self {
+        Detector { settings, baseline: None, max_rise: 0.0 }
+    }
+
+    /// Analyze the next window of raw samples.
+    pub fn process(&mut self, samples: &[u8]) -> WindowDetection {
+        let gate = self.settings.energy_gate_db;
+        if gate.is_some_and(|gate| coarse_power_db(samples, self.settings.energy_gate_decimation) < gate) {
+            return WindowDetection { idle: true, strength: None, detected: false };
+        }
+
+        let strength = samples.iter().max().map(|&max| sample_db(max));
+        let detected = strength.is_some_and(|strength| self.is_detection(strength));
+        WindowDetection { idle: false, strength, detected }
+    }
+
+    /// Rise detection's baseline after the last window, None before the first or without it.
+    pub fn baseline(&self) -> Option<f64> {
+        self.baseline
+    }
+
+    /// Largest rise above the baseline seen so far.
+    pub fn max_rise(&self) -> f64 {
+        self.max_rise
+    }
+
+    // Above detection_threshold, or with rise_detection a rise of delta_db over the baseline,
+    // which then moves towards this window's strength
+    fn is_detection(&mut self, strength: f64) -> bool {
+        let Some(rise_detection) = self.settings.rise_detection.as_ref() else {
+            return strength > self.settings.detection_threshold;
+        };
+        let baseline = *self.baseline.get_or_insert(strength);
+        let rise = strength - baseline;
+        self.max_rise = self.max_rise.max(rise);
+        self.baseline = Some(baseline + rise_detection.baseline_alpha * rise);
+        rise > rise_detection.delta_db
+    }
+}
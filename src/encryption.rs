@@ -0,0 +1,73 @@
+use serde::{Serialize, Deserialize};
+use std::fs::File;
+use std::io::{BufReader, Write};
+
+pub const ENCRYPTED_SUFFIX: &str = ".age";
+
+// Environment variable holding an age identity (private key) for the decrypt subcommand
+pub const IDENTITY_ENV_VAR: &str = "ZWAVE_AGE_IDENTITY";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptionConfig {
+    #[serde(default)]
+    pub recipient: Option<String>,
+    // Write plaintext when the recipient is missing or invalid instead of failing
+    #[serde(default)]
+    pub allow_unencrypted: bool,
+}
+
+/// Write a result artifact, encrypting it to the configured age recipient when encryption is set up.
+/// Returns the path that was actually written.
+pub fn write_artifact(encryption: Option<&EncryptionConfig>, path: &str, contents: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+    let Some(encryption) = encryption else {
+        File::create(path)?.write_all(contents)?;
+        return Ok(path.to_string());
+    };
+
+    match parse_recipient(encryption) {
+        Ok(recipient) => {
+            let encrypted_path = format!("{}{}", path, ENCRYPTED_SUFFIX);
+            let encryptor = age::Encryptor::with_recipients(std::iter::once(&recipient as &dyn age::Recipient))?;
+            let mut writer = encryptor.wrap_output(File::create(&encrypted_path)?)?;
+            writer.write_all(contents)?;
+            writer.finish()?;
+            Ok(encrypted_path)
+        }
+        Err(reason) if encryption.allow_unencrypted => {
+            println!("Warning: {}, writing {} unencrypted", reason, path);
+            File::create(path)?.write_all(contents)?;
+            Ok(path.to_string())
+        }
+        Err(reason) => Err(format!(
+            "{}, refusing to write {} unencrypted (set allow_unencrypted to opt in)",
+            reason, path
+        ).into()),
+    }
+}
+
+fn parse_recipient(encryption: &EncryptionConfig) -> Result<age::x25519::Recipient, String> {
+    let recipient = encryption.recipient.as_deref()
+        .ok_or_else(|| String::from("No encryption recipient configured"))?;
+    recipient.trim().parse()
+        .map_err(|e| format!("Invalid encryption recipient: {}", e))
+}
+
+/// Decrypt an artifact written by write_artifact and copy the plaintext to `output`.
+/// The identity is read from `identity_path` if given, otherwise from ZWAVE_AGE_IDENTITY.
+pub fn decrypt_artifact(path: &str, identity_path: Option<&str>, output: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    let identity_file = match identity_path {
+        Some(identity_path) => age::IdentityFile::from_file(identity_path.to_string())?,
+        None => {
+            let key = std::env::var(IDENTITY_ENV_VAR)
+                .map_err(|_| format!("No identity given: pass --identity <file> or set {}", IDENTITY_ENV_VAR))?;
+            age::IdentityFile::from_buffer(key.as_bytes())?
+        }
+    };
+    let identities = identity_file.into_identities()?;
+
+    let decryptor = age::Decryptor::new_buffered(BufReader::new(File::open(path)?))?;
+    let mut reader = decryptor.decrypt(identities.iter().map(|identity| identity.as_ref() as &dyn age::Identity))?;
+    std::io::copy(&mut reader, output)?;
+
+    Ok(())
+}
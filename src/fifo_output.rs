@@ -0,0 +1,65 @@
+// Streams each result as one JSON line into a pre-created named pipe, for downstream processes
+// that read continuously instead of polling a file.
+//
+// The pipe is opened without blocking, so a missing reader only drops that record. The writer is
+// kept open between records; if the reader goes away (EPIPE) it is closed and reopened for the
+// next record.
+
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::sync::Mutex;
+
+static PIPE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Write one record, logging rather than failing when there is no reader.
+pub fn send(path: &str, record: &str) {
+    let mut pipe = PIPE.lock().unwrap();
+    if pipe.is_none() {
+        match open(path) {
+            Ok(file) => *pipe = Some(file),
+            Err(e) if no_reader(&e) => {
+                scan_println!("No reader on FIFO {}, dropping result", path);
+                return;
+            }
+            Err(e) => {
+                scan_println!("Failed to open FIFO {}: {}", path, e);
+                return;
+            }
+        }
+    }
+
+    let Some(file) = pipe.as_mut() else {
+        return;
+    };
+    if let Err(e) = file.write_all(format!("{}\n", record).as_bytes()) {
+        match e.kind() {
+            ErrorKind::BrokenPipe => scan_println!("FIFO reader on {} went away, dropping result", path),
+            ErrorKind::WouldBlock => scan_println!("FIFO {} is full, dropping result", path),
+            _ => scan_println!("Failed to write to FIFO {}: {}", path, e),
+        }
+        *pipe = None;
+    }
+}
+
+#[cfg(unix)]
+fn open(path: &str) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    std::fs::OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open(path)
+}
+
+#[cfg(not(unix))]
+fn open(_path: &str) -> std::io::Result<File> {
+    Err(std::io::Error::new(ErrorKind::Unsupported, "FIFO output needs a Unix system"))
+}
+
+// A non-blocking open fails with ENXIO while nobody has the pipe open for reading
+#[cfg(unix)]
+fn no_reader(error: &std::io::Error) -> bool {
+    error.raw_os_error() == Some(libc::ENXIO)
+}
+
+#[cfg(not(unix))]
+fn no_reader(_error: &std::io::Error) -> bool {
+    false
+}
@@ -0,0 +1,148 @@
+// Long-term noise-floor log for site characterization. Each scan appends one JSON line per channel
+// to the history file, and entries older than the retention period are dropped as new ones are
+// added. `zwave_module history` renders the file as an ASCII plot per channel, or as CSV.
+//
+// The file is read back and appended to on every scan, which an age-encrypted file doesn't allow
+// without the private key, so it is always plaintext. With encryption configured that needs its
+// own opt-in, allow_plaintext, separate from encryption's allow_unencrypted fallback.
+
+use crate::{flag_value, Config};
+use crate::encryption::EncryptionConfig;
+use serde::{Serialize, Deserialize};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PLOT_WIDTH: usize = 60;
+const PLOT_HEIGHT: usize = 10;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NoiseHistoryConfig {
+    #[serde(default = "default_path")]
+    pub path: String,
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u64,
+    // Keep the history even though encryption is configured for everything else
+    #[serde(default)]
+    pub allow_plaintext: bool,
+}
+
+fn default_path() -> String {
+    String::from("zwave_noise_history.jsonl")
+}
+
+fn default_retention_days() -> u64 {
+    30
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    timestamp: u64,
+    frequency: u64,
+    noise_floor_db: f64,
+}
+
+/// Refuse a plaintext history when encryption is configured without allow_plaintext.
+pub fn check_encryption(config: &NoiseHistoryConfig, encryption: Option<&EncryptionConfig>) -> Result<(), Box<dyn std::error::Error>> {
+    if encryption.is_some() && !config.allow_plaintext {
+        return Err(format!(
+            "noise_history keeps {} unencrypted, refusing with encryption configured (set noise_history.allow_plaintext to opt in)",
+            config.path
+        ).into());
+    }
+    Ok(())
+}
+
+/// Append a noise floor measurement, dropping entries older than the retention period.
+pub fn record(config: &NoiseHistoryConfig, encryption: Option<&EncryptionConfig>, frequency: u64, noise_floor_db: f64) -> Result<(), Box<dyn std::error::Error>> {
+    check_encryption(config, encryption)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let oldest = now.saturating_sub(config.retention_days * 24 * 60 * 60);
+    let mut entries = read_entries(&config.path)?;
+    let expired = entries.iter().any(|entry| entry.timestamp < oldest);
+    let entry = Entry { timestamp: now, frequency, noise_floor_db };
+
+    if expired {
+        entries.retain(|entry| entry.timestamp >= oldest);
+        entries.push(entry);
+        let mut lines = String::new();
+        for entry in &entries {
+            lines.push_str(&serde_json::to_string(entry)?);
+            lines.push('\n');
+        }
+        std::fs::write(&config.path, lines)?;
+    } else {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&config.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    }
+    Ok(())
+}
+
+fn read_entries(path: &str) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    // A line cut short by a crash mid-write is skipped rather than failing the whole history
+    Ok(contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+// zwave_module history [--frequency <hz>] [--csv]
+pub fn run_history(config: &Config, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = config.noise_history.as_ref().map_or_else(default_path, |history| history.path.clone());
+    let only_frequency: Option<u64> = flag_value(args, "--frequency")?.map(str::parse).transpose()?;
+
+    let mut entries = read_entries(&path)?;
+    entries.retain(|entry| only_frequency.is_none_or(|frequency| entry.frequency == frequency));
+    entries.sort_by_key(|entry| (entry.frequency, entry.timestamp));
+    if entries.is_empty() {
+        return Err(format!("No noise floor history in {}", path).into());
+    }
+
+    if args.iter().any(|arg| arg == "--csv") {
+        println!("timestamp,frequency,noise_floor_db");
+        for entry in &entries {
+            println!("{},{},{:.2}", entry.timestamp, entry.frequency, entry.noise_floor_db);
+        }
+        return Ok(());
+    }
+
+    for channel in entries.chunk_by(|a, b| a.frequency == b.frequency) {
+        plot(channel);
+    }
+    Ok(())
+}
+
+// Each column is the mean of the entries falling in its slice of the time span
+fn plot(entries: &[Entry]) {
+    let first = entries[0].timestamp;
+    let last = entries[entries.len() - 1].timestamp;
+    let span = (last - first).max(1);
+
+    let mut sums = [(0.0, 0usize); PLOT_WIDTH];
+    for entry in entries {
+        let column = ((entry.timestamp - first) as f64 / span as f64 * (PLOT_WIDTH - 1) as f64) as usize;
+        sums[column].0 += entry.noise_floor_db;
+        sums[column].1 += 1;
+    }
+    let columns: Vec<Option<f64>> = sums.iter().map(|&(sum, count)| (count > 0).then(|| sum / count as f64)).collect();
+
+    let min = entries.iter().map(|entry| entry.noise_floor_db).fold(f64::MAX, f64::min);
+    let max = entries.iter().map(|entry| entry.noise_floor_db).fold(f64::MIN, f64::max);
+    let range = (max - min).max(0.1);
+
+    println!("{:.3} MHz, {} entries over {:.1} hours", entries[0].frequency as f64 / 1_000_000.0, entries.len(), (last - first) as f64 / 3600.0);
+    for row in (0..PLOT_HEIGHT).rev() {
+        let level = min + range * row as f64 / (PLOT_HEIGHT - 1) as f64;
+        let line: String = columns.iter()
+            .map(|column| match column {
+                Some(value) if ((value - min) / range * (PLOT_HEIGHT - 1) as f64).round() as usize == row => '*',
+                _ => ' ',
+            })
+            .collect();
+        println!("{:>7.1} dB |{}", level, line);
+    }
+    println!("{:>10} +{}", "", "-".repeat(PLOT_WIDTH));
+    println!("{:>10}  {:<width$}{}", "", first, last, width = PLOT_WIDTH - last.to_string().len());
+    println!();
+}
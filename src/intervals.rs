@@ -0,0 +1,191 @@
+//! Helpers for the detection intervals reported in `zwave_durations`.
+//!
+//! An interval is a half-open `(start, end)` pair in whole seconds from the start of a scan,
+//! so `(3, 5)` covers seconds 3 and 4 and touches, but does not overlap, `(5, 7)`.
+
+use std::fmt;
+
+pub type Interval = (u64, u64);
+
+/// Merge intervals whose start falls within `max_gap` seconds of the end of the previous one.
+/// The result is sorted by start and never contains overlapping or touching intervals.
+pub fn merge_intervals(mut intervals: Vec<Interval>, max_gap: u64) -> Vec<Interval> {
+    if intervals.is_empty() {
+        return Vec::new();
+    }
+
+    intervals.sort_unstable_by_key(|interval| interval.0);
+    let mut merged = vec![intervals[0]];
+
+    for &(start, end) in &intervals[1..] {
+        let last = merged.last_mut().unwrap();
+
+        if start <= last.1.saturating_add(max_gap) {
+            last.1 = last.1.max(end);
+        } else {
+            merged.push((start, end));
+        }
+    }
+
+    merged
+}
+
+/// Total number of seconds covered by the intervals, counting overlapping and touching spans once.
+pub fn covered_duration(intervals: &[Interval]) -> u64 {
+    merge_intervals(intervals.to_vec(), 0)
+        .iter()
+        .map(|&(start, end)| end.saturating_sub(start))
+        .sum()
+}
+
+/// Clip the intervals to `window`, dropping any that fall entirely outside it.
+pub fn clip_to_window(intervals: &[Interval], window: Interval) -> Vec<Interval> {
+    intervals.iter()
+        .map(|&(start, end)| (start.max(window.0), end.min(window.1)))
+        .filter(|&(start, end)| start < end)
+        .collect()
+}
+
+/// Run-length encode per-window activity into `(active, windows)` runs covering every window in order.
+pub fn run_lengths(states: &[bool]) -> Vec<(bool, u64)> {
+    let mut runs: Vec<(bool, u64)> = Vec::new();
+    for &state in states {
+        match runs.last_mut() {
+            Some((active, length)) if *active == state => *length += 1,
+            _ => runs.push((state, 1)),
+        }
+    }
+    runs
+}
+
+/// Format intervals as the `"a-b,c-d"` string used by `zwave_durations`.
+pub fn format_intervals(intervals: &[Interval]) -> String {
+    intervals.iter()
+        .map(|&(start, end)| format!("{}-{}", start, end))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Format at most `cap` intervals, returning the string and whether anything was left out.
+///
+/// When there are more than `cap` intervals, the `cap` longest are kept (ties go to the earlier
+/// interval), listed in time order and followed by a `+K more` entry for the `K` that were dropped,
+/// e.g. `"0-3,10-20,+2 more"`.
+pub fn format_intervals_capped(intervals: &[Interval], cap: usize) -> (String, bool) {
+    if intervals.len() <= cap {
+        return (format_intervals(intervals), false);
+    }
+
+    let mut by_length: Vec<Interval> = intervals.to_vec();
+    by_length.sort_by_key(|&(start, end)| (std::cmp::Reverse(end.saturating_sub(start)), start));
+    by_length.truncate(cap);
+    by_length.sort_unstable_by_key(|interval| interval.0);
+
+    let more = format!("+{} more", intervals.len() - cap);
+    let durations = if by_length.is_empty() {
+        more
+    } else {
+        format!("{},{}", format_intervals(&by_length), more)
+    };
+
+    (durations, true)
+}
+
+/// Parse a `"a-b,c-d"` string produced by [`format_intervals`]. An empty string is no intervals.
+pub fn parse_intervals(durations: &str) -> Result<Vec<Interval>, ParseIntervalsError> {
+    if durations.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    durations.split(',')
+        .map(|part| {
+            let invalid = || ParseIntervalsError(part.trim().to_string());
+            let (start, end) = part.trim().split_once('-').ok_or_else(invalid)?;
+            let start: u64 = start.parse().map_err(|_| invalid())?;
+            let end: u64 = end.parse().map_err(|_| invalid())?;
+            if start > end {
+                return Err(invalid());
+            }
+            Ok((start, end))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIntervalsError(String);
+
+impl fmt::Display for ParseIntervalsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid interval \"{}\", expected start-end", self.0)
+    }
+}
+
+impl std::error::Error for ParseIntervalsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn intervals() -> impl Strategy<Value = Vec<Interval>> {
+        prop::collection::vec((0u64..1000, 0u64..50).prop_map(|(start, length)| (start, start + length)), 0..40)
+    }
+
+    #[test]
+    fn touching_intervals_are_counted_once() {
+        assert_eq!(merge_intervals(vec![(5, 7), (3, 5)], 0), vec![(3, 7)]);
+        assert_eq!(covered_duration(&[(3, 5), (5, 7)]), 4);
+        assert_eq!(covered_duration(&[(3, 5), (4, 7), (7, 8)]), 5);
+    }
+
+    #[test]
+    fn capped_at_the_cap_is_unchanged() {
+        let intervals = [(0, 3), (10, 20), (30, 31)];
+        assert_eq!(format_intervals_capped(&intervals, 3), (String::from("0-3,10-20,30-31"), false));
+    }
+
+    #[test]
+    fn capped_just_over_the_cap_drops_the_shortest() {
+        let intervals = [(0, 3), (10, 20), (30, 31), (40, 42)];
+        assert_eq!(format_intervals_capped(&intervals, 3), (String::from("0-3,10-20,40-42,+1 more"), true));
+    }
+
+    #[test]
+    fn capped_ties_go_to_the_earlier_interval() {
+        let intervals = [(0, 2), (10, 12), (20, 22), (30, 40)];
+        assert_eq!(format_intervals_capped(&intervals, 2), (String::from("0-2,30-40,+2 more"), true));
+    }
+
+    #[test]
+    fn capped_at_zero_is_only_the_count() {
+        assert_eq!(format_intervals_capped(&[(0, 2), (10, 12)], 0), (String::from("+2 more"), true));
+        assert_eq!(format_intervals_capped(&[], 0), (String::new(), false));
+    }
+
+    proptest! {
+        #[test]
+        fn merge_is_idempotent(intervals in intervals(), max_gap in 0u64..10) {
+            let merged = merge_intervals(intervals, max_gap);
+            prop_assert_eq!(merge_intervals(merged.clone(), max_gap), merged);
+        }
+
+        #[test]
+        fn merge_is_sorted_and_non_overlapping(intervals in intervals(), max_gap in 0u64..10) {
+            let merged = merge_intervals(intervals, max_gap);
+            for pair in merged.windows(2) {
+                prop_assert!(pair[0].1 + max_gap < pair[1].0, "{:?} and {:?} overlap or touch", pair[0], pair[1]);
+            }
+        }
+
+        #[test]
+        fn covered_duration_fits_in_window(intervals in intervals(), start in 0u64..1000, length in 0u64..500) {
+            let window = (start, start + length);
+            prop_assert!(covered_duration(&clip_to_window(&intervals, window)) <= length);
+        }
+
+        #[test]
+        fn parse_inverts_format(intervals in intervals()) {
+            prop_assert_eq!(parse_intervals(&format_intervals(&intervals)).unwrap(), intervals);
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod analysis;
+pub mod detector;
+pub mod intervals;
+pub mod streaming;
@@ -1,24 +1,565 @@
+// println! prefixed with the current scan ID, for log lines about a scan
+macro_rules! scan_println {
+    ($($arg:tt)*) => {
+        println!("{}{}", crate::scan_id::log_prefix(), format!($($arg)*))
+    };
+}
+
+mod ab_test;
+mod budget;
+mod compare;
+mod encryption;
+mod fifo_output;
+mod history;
+mod on_detect;
+mod output_format;
+mod placement;
+mod presence;
+mod radio_errors;
+mod scan;
+mod scan_id;
+mod shutdown;
+mod source;
+mod step_script;
+mod survey;
+mod syslog_output;
+#[cfg(feature = "tui")]
+mod tui;
+
+use ab_test::AbTestConfig;
+use compare::InterfererSkipConfig;
+use encryption::EncryptionConfig;
 use hackrfone::{HackRfOne, UnknownMode};
+use history::NoiseHistoryConfig;
+use on_detect::OnDetect;
+use output_format::OutputFormat;
+use presence::PresenceScoreConfig;
+use radio_errors::ErrorAction;
+use scan::WindowedScan;
+use source::{SampleSource, StdinSource};
+use step_script::{ScriptEnd, ScriptStep, StepSummary};
+use syslog_output::{SyslogConfig, SyslogOutput};
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::time::{Instant, Duration};
 use std::fs::File;
-use std::io::Write;
-use std::collections::HashMap;
 use tokio::time::sleep;
+use zwave_module::analysis::{analyze_samples, byte_histogram, estimate_noise_floor, noise_floor_interval};
+use zwave_module::detector::{Detector, DetectorSettings, RiseDetection};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 struct SignalData {
     frequency: f64,
     is_signal_detected: bool,
     max_signal_strength: f64,
+    // Strongest sample over everything analyzed, detected or not, relative to a full-scale sample.
+    // Absent when no samples were analyzed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_strength_dbfs: Option<f64>,
+    // raw_strength_dbfs plus calibration_offset_db, only when calibration is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    calibrated_dbm: Option<f64>,
+    // Weakest signal the detection would flag, with calibration, see min_detectable_dbm()
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_detectable_dbm: Option<f64>,
     zwave_durations: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dynamic_range_db: Option<f64>,
+    // Median sample strength with its 95% confidence interval, see analysis::noise_floor_interval
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noise_floor_db: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noise_floor_ci_low: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noise_floor_ci_high: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_max_sample: Option<u8>,
+    // null for a window lost to a radio error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_max_per_window: Option<Vec<Option<u8>>>,
+    // I/Q sample pairs captured in each window, and how many a full window should hold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_counts: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_sample_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeline: Option<Vec<TimelineRun>>,
+    // Set when zwave_durations was capped by max_duration_intervals, intervals then has the full list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    durations_truncated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intervals: Option<Vec<(u64, u64)>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_period_secs: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period_confidence: Option<f64>,
+    // Set when the scan was stopped early by SIGINT/SIGTERM and only covers part of scan_duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interrupted: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step_breakdown: Option<Vec<StepSummary>>,
+    // Start of the first detection interval, null when nothing was detected. Like zwave_durations it
+    // places a window at the whole second its capture ended, so the two always agree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_detection_secs: Option<Option<f64>>,
+    // Set when the capture ran with the amp and a high LNA gain, so clipped data can be traced back to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    high_gain_with_amp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receive_safe: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scan_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    energy_gate: Option<EnergyGateSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guard: Option<GuardSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle: Option<ThrottleSummary>,
+    // Key/value pairs from the context_path file, read when the result was written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<serde_json::Map<String, serde_json::Value>>,
+    // Windows that would be detections at each threshold_sweep threshold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    threshold_sweep: Option<Vec<ThresholdCount>>,
+    // Baseline at the end of the scan and the largest rise above it, with rise_detection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rise: Option<RiseSummary>,
+    // What the radio was set to for the last capture, absent when samples didn't come from the radio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    radio_config: Option<RadioConfig>,
+}
+
+// How many windows the energy gate marked idle without running the full analysis
+#[derive(Serialize, Deserialize)]
+struct EnergyGateSummary {
+    windows: usize,
+    idle_windows: usize,
+    idle_fraction: f64,
+}
+
+impl EnergyGateSummary {
+    fn new(windows: usize, idle_windows: usize) -> Self {
+        EnergyGateSummary { windows, idle_windows, idle_fraction: idle_windows as f64 / windows.max(1) as f64 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RiseSummary {
+    baseline_db: f64,
+    max_rise_db: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ThrottleSummary {
+    analysis_duty_cycle: f64,
+    analysis_secs: f64,
+    throttle_secs: f64,
+}
+
+// Candidate detection thresholds from start to end inclusive, step apart
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThresholdSweepConfig {
+    start: f64,
+    end: f64,
+    step: f64,
+}
+
+// Upper bound on the thresholds in a sweep, so a tiny step can't allocate without limit
+const THRESHOLD_SWEEP_MAX_STEPS: f64 = 1000.0;
+
+impl ThresholdSweepConfig {
+    // Number of steps from start to end. The small tolerance keeps end in the sweep when
+    // (end - start) / step comes out just under a whole number, e.g. 45 to 48 in steps of 0.1.
+    fn steps(&self) -> f64 {
+        ((self.end - self.start) / self.step + 1e-9).floor()
+    }
+
+    fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.step <= 0.0 || self.end < self.start {
+            return Err("threshold_sweep needs a positive step and an end no lower than its start".into());
+        }
+        if self.steps() >= THRESHOLD_SWEEP_MAX_STEPS {
+            return Err(format!(
+                "threshold_sweep from {} to {} in steps of {} has more than {} thresholds, use a larger step",
+                self.start, self.end, self.step, THRESHOLD_SWEEP_MAX_STEPS
+            ).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ThresholdCount {
+    threshold: f64,
+    active_windows: usize,
+}
+
+// Detections in the guard intervals, kept out of the main results. With guards all offsets, here
+// and in zwave_durations, count from the start of the leading guard.
+#[derive(Serialize, Deserialize)]
+struct GuardSummary {
+    leading_secs: u64,
+    trailing_secs: u64,
+    // Start and end of the scheduled scan period
+    main_window: (u64, u64),
+    leading_durations: String,
+    trailing_durations: String,
+}
+
+// One run of consecutive one-second windows with the same detection state
+#[derive(Serialize, Deserialize)]
+struct TimelineRun {
+    active: bool,
+    duration: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Config {
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
     instant_scan: bool,
     start_after_duration: u64,
     scan_duration: u64,
+    #[serde(default = "default_frequency")]
+    frequency: u64,
+    #[serde(default = "default_sample_rate")]
+    sample_rate: u32,
+    #[serde(default = "default_instant_duration")]
+    instant_duration: u64,
+    #[serde(default = "default_lna_gain")]
+    lna_gain: i32,
+    #[serde(default = "default_vga_gain")]
+    vga_gain: i32,
+    #[serde(default)]
+    gain_clamp: GainClamp,
+    #[serde(default)]
+    emit_dynamic_range: bool,
+    #[serde(default)]
+    emit_noise_floor_ci: bool,
+    // Record the raw maximum sample byte, before any dB conversion
+    #[serde(default)]
+    debug_raw_max: bool,
+    #[serde(default)]
+    emit_timeline: bool,
+    // Record the I/Q sample pairs captured per window, to spot dropped samples
+    #[serde(default)]
+    debug_sample_counts: bool,
+    // Maximum number of intervals written to zwave_durations
+    #[serde(default)]
+    max_duration_intervals: Option<usize>,
+    #[serde(default)]
+    estimate_period: bool,
+    #[serde(default = "default_min_detections_for_period")]
+    min_detections_for_period: usize,
+    #[serde(default)]
+    encryption: Option<EncryptionConfig>,
+    #[serde(default = "default_placement_capture_ms")]
+    placement_capture_ms: u64,
+    #[serde(default)]
+    placement_beep: bool,
+    // Overrides for how radio errors are handled, keyed by error kind (e.g. "timeout", "no_device")
+    #[serde(default)]
+    radio_error_actions: HashMap<String, ErrorAction>,
+    #[serde(default = "default_radio_error_max_retries")]
+    radio_error_max_retries: u32,
+    #[serde(default = "default_detection_threshold")]
+    detection_threshold: f64,
+    #[serde(default)]
+    ab_test: Option<AbTestConfig>,
+    #[serde(default)]
+    syslog: Option<SyslogConfig>,
+    // How long to wait for a scan to wrap up after SIGINT/SIGTERM before exiting anyway
+    #[serde(default = "default_shutdown_grace_secs")]
+    shutdown_grace_secs: u64,
+    #[serde(default)]
+    compare_frequencies: Vec<u64>,
+    #[serde(default = "default_compare_rounds")]
+    compare_rounds: u64,
+    #[serde(default)]
+    interferer_skip: Option<InterfererSkipConfig>,
+    // One of compare_frequencies that the other channels are reported relative to
+    #[serde(default)]
+    reference_frequency: Option<u64>,
+    #[serde(default)]
+    step_script: Vec<ScriptStep>,
+    #[serde(default)]
+    step_script_end: ScriptEnd,
+    #[serde(default)]
+    emit_first_detection: bool,
+    #[serde(default = "default_amp_enable")]
+    amp_enable: bool,
+    // LNA gain at or above which amp_enable needs allow_high_gain_with_amp
+    #[serde(default = "default_high_lna_threshold")]
+    high_lna_threshold: u16,
+    #[serde(default)]
+    allow_high_gain_with_amp: bool,
+    #[serde(default)]
+    antenna_power: bool,
+    // Never enable the amp or antenna power, for sessions alongside transmit experiments
+    #[serde(default)]
+    receive_safe: bool,
+    #[serde(default)]
+    scan_uuid: bool,
+    // Shell command run on detection, see on_detect.rs for the environment it gets
+    #[serde(default)]
+    on_detect_command: Option<String>,
+    // Minimum time between runs of on_detect_command, so a persistent signal doesn't spawn it every window
+    #[serde(default = "default_on_detect_cooldown_secs")]
+    on_detect_cooldown_secs: u64,
+    // Write a 0-100 occupancy score to zwave_score.json after each scan, see presence.rs
+    #[serde(default)]
+    presence_score: Option<PresenceScoreConfig>,
+    // Append each scan's noise floor to a history file, see history.rs
+    #[serde(default)]
+    noise_history: Option<NoiseHistoryConfig>,
+    // dBm at the antenna port for a full-scale sample, from calibration against a known source
+    #[serde(default)]
+    calibration_offset_db: Option<f64>,
+    // Detect on a rise above a sliding baseline instead of detection_threshold, in scans made of windows
+    #[serde(default)]
+    rise_detection: Option<RiseDetection>,
+    // Fraction of the time analysis may keep the CPU busy in scans made of windows, above 0 and up
+    // to 1 for no throttling; after each window it sleeps for long enough to bring the average down to this
+    #[serde(default)]
+    analysis_duty_cycle: Option<f64>,
+    // JSON object of external context (temperature, occupancy, ...) merged into each result
+    #[serde(default)]
+    context_path: Option<String>,
+    // Also count the windows each of a range of thresholds would flag, for picking a threshold
+    #[serde(default)]
+    threshold_sweep: Option<ThresholdSweepConfig>,
+    // Pre-created named pipe that also gets each result as a JSON line
+    #[serde(default)]
+    fifo_path: Option<String>,
+    // Format of the result on stdout and in the result file, "json" or "logfmt". Result files in
+    // logfmt get a .log extension.
+    #[serde(default)]
+    output_format: OutputFormat,
+    // Extra capture before and after the scheduled scan, so bursts straddling its edges aren't cut
+    // off. The leading guard is capped at start_after_duration.
+    #[serde(default)]
+    leading_guard_secs: u64,
+    #[serde(default)]
+    trailing_guard_secs: u64,
+    // Windows whose coarse power is below this many dB are recorded as idle without full analysis
+    #[serde(default)]
+    energy_gate_db: Option<f64>,
+    // Only every Nth sample goes into the coarse power estimate
+    #[serde(default = "default_energy_gate_decimation")]
+    energy_gate_decimation: usize,
+    // Run the instant scan this many times, keeping the radio open and configured between scans
+    #[serde(default)]
+    repeat_count: Option<u64>,
+    #[serde(default)]
+    repeat_interval_secs: u64,
+    // Switch the amp and antenna power off after each scan, re-arming the radio at the next one
+    #[serde(default)]
+    idle_between_scans: bool,
+    // Total bytes the session may capture across all scans, checked between scans. Batch runs
+    // take it from the first config.
+    #[serde(default)]
+    scan_budget_bytes: Option<u64>,
+    // Length of the detection scan phase of the survey command
+    #[serde(default = "default_survey_scan_secs")]
+    survey_scan_secs: u64,
+}
+
+fn default_frequency() -> u64 {
+    868_400_000 // 868.4 MHz, EU Z-Wave
+}
+
+fn default_sample_rate() -> u32 {
+    10_000_000 // 10 MS/s
+}
+
+fn default_instant_duration() -> u64 {
+    5
+}
+
+fn default_placement_capture_ms() -> u64 {
+    150
+}
+
+fn default_radio_error_max_retries() -> u32 {
+    3
+}
+
+fn default_detection_threshold() -> f64 {
+    50.0
+}
+
+fn default_min_detections_for_period() -> usize {
+    4
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    10
+}
+
+fn default_compare_rounds() -> u64 {
+    10
+}
+
+fn default_amp_enable() -> bool {
+    true
+}
+
+fn default_high_lna_threshold() -> u16 {
+    32
+}
+
+fn default_energy_gate_decimation() -> usize {
+    64
+}
+
+fn default_on_detect_cooldown_secs() -> u64 {
+    60
+}
+
+fn default_survey_scan_secs() -> u64 {
+    600
+}
+
+fn default_lna_gain() -> i32 {
+    16
+}
+
+fn default_vga_gain() -> i32 {
+    20
+}
+
+// How to handle a requested gain that the HackRF can't apply as-is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum GainClamp {
+    Error,
+    #[default]
+    Clamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gains {
+    pub lna: u16,
+    pub vga: u16,
+    pub amp: bool,
+    // Antenna port power (bias-tee)
+    pub antenna_power: bool,
+}
+
+// LNA: 0-40 dB in 8 dB steps, VGA: 0-62 dB in 2 dB steps
+const LNA_GAIN_MAX: i32 = 40;
+const LNA_GAIN_STEP: i32 = 8;
+const VGA_GAIN_MAX: i32 = 62;
+const VGA_GAIN_STEP: i32 = 2;
+
+impl Config {
+    fn detector_settings(&self) -> DetectorSettings {
+        DetectorSettings {
+            detection_threshold: self.detection_threshold,
+            rise_detection: self.rise_detection.clone(),
+            energy_gate_db: self.energy_gate_db,
+            energy_gate_decimation: self.energy_gate_decimation,
+        }
+    }
+
+    // Whether scans made of windows need the sample histogram for a noise floor
+    fn needs_noise_floor(&self) -> bool {
+        self.emit_dynamic_range || self.emit_noise_floor_ci || self.presence_score.is_some() || self.noise_history.is_some()
+            || (self.calibration_offset_db.is_some() && self.rise_detection.is_some())
+    }
+}
+
+fn resolve_gains(config: &Config) -> Result<Gains, Box<dyn std::error::Error>> {
+    let lna = resolve_gain("LNA", config.lna_gain, LNA_GAIN_MAX, LNA_GAIN_STEP, config.gain_clamp)?;
+    let vga = resolve_gain("VGA", config.vga_gain, VGA_GAIN_MAX, VGA_GAIN_STEP, config.gain_clamp)?;
+    check_amp_with_lna(config, lna)?;
+    Ok(Gains { lna, vga, amp: config.amp_enable, antenna_power: config.antenna_power }.receive_safe(config))
+}
+
+impl Gains {
+    // In receive-safe mode the amp and antenna power stay off whatever else is configured
+    fn receive_safe(self, config: &Config) -> Gains {
+        if !config.receive_safe {
+            return self;
+        }
+
+        let suppressed: Vec<&str> = [("amplifier", self.amp), ("antenna power", self.antenna_power)]
+            .into_iter()
+            .filter_map(|(name, enabled)| enabled.then_some(name))
+            .collect();
+        if suppressed.is_empty() {
+            println!("Receive-safe mode: amplifier and antenna power off");
+        } else {
+            println!("Receive-safe mode: suppressed {}", suppressed.join(" and "));
+        }
+
+        Gains { amp: false, antenna_power: false, ..self }
+    }
+}
+
+// The RF amp on top of a high LNA gain can damage the front end near a transmitter,
+// so that combination has to be acknowledged explicitly
+fn check_amp_with_lna(config: &Config, lna: u16) -> Result<(), Box<dyn std::error::Error>> {
+    if high_gain_with_amp(config, lna) && !config.allow_high_gain_with_amp {
+        return Err(format!(
+            "amp_enable with an LNA gain of {} dB (>= {} dB) risks damaging the HackRF front end near \
+             a transmitter and will clip strong signals. Lower lna_gain, disable amp_enable, or set \
+             allow_high_gain_with_amp to true to proceed anyway",
+            lna, config.high_lna_threshold
+        ).into());
+    }
+    Ok(())
+}
+
+fn high_gain_with_amp(config: &Config, lna: u16) -> bool {
+    config.amp_enable && !config.receive_safe && lna >= config.high_lna_threshold
+}
+
+// I/Q samples are signed bytes, so a sample at either end of the i8 range means the front end
+// clipped during the capture. 0xFF is -1, right next to zero, and says nothing about clipping.
+fn is_clipped(raw_samples: &[u8]) -> bool {
+    raw_samples.iter().any(|&sample| sample as i8 == i8::MIN || sample as i8 == i8::MAX)
+}
+
+fn warn_clipped(config: &Config, gains: Gains) {
+    if high_gain_with_amp(config, gains.lna) {
+        scan_println!(
+            "Warning: samples clipped with the amp enabled and LNA gain at {} dB, disable amp_enable or lower lna_gain",
+            gains.lna
+        );
+    } else {
+        scan_println!("Warning: samples clipped, consider lowering the gain");
+    }
+}
+
+fn resolve_gain(name: &str, requested: i32, max: i32, step: i32, mode: GainClamp) -> Result<u16, Box<dyn std::error::Error>> {
+    let valid = (0..=max).contains(&requested) && requested % step == 0;
+    if valid {
+        return Ok(requested as u16);
+    }
+
+    match mode {
+        GainClamp::Error => Err(format!(
+            "{} gain {} dB is invalid (expected 0-{} dB in {} dB steps)",
+            name, requested, max, step
+        ).into()),
+        GainClamp::Clamp => {
+            // Snap to the nearest step, rounding halfway values down
+            let clamped = requested.clamp(0, max);
+            let snapped = ((clamped + (step - 1) / 2) / step * step).min(max);
+            println!("Warning: {} gain {} dB is invalid, using {} dB instead", name, requested, snapped);
+            Ok(snapped as u16)
+        }
+    }
+}
+
+fn version_string() -> String {
+    format!(
+        "{} {} (commit {}, hackrfone {}, {})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        env!("ZWAVE_GIT_COMMIT"),
+        env!("ZWAVE_HACKRFONE_VERSION"),
+        env!("ZWAVE_TARGET"),
+    )
 }
 
 fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
@@ -28,21 +569,130 @@ fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>>
     Ok(config)
 }
 
-fn scan_freq(mut radio: HackRfOne<UnknownMode>, frequency: u64, sample_rate: u32, duration: Duration) -> Vec<u8> {
-    radio.set_freq(frequency).expect("Failed to set frequency");
-    radio.set_sample_rate(sample_rate, 1).expect("Failed to set sample rate");
-    radio.set_amp_enable(true).expect("Failed to enable amplifier");
-    radio.set_lna_gain(16).expect("Failed to set LNA gain");
-    radio.set_vga_gain(20).expect("Failed to set VGA gain");
+// Settings that can't be checked by deserializing alone
+fn check_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(history) = config.noise_history.as_ref() {
+        history::check_encryption(history, config.encryption.as_ref())?;
+    }
+    if let Some(sweep) = config.threshold_sweep.as_ref() {
+        sweep.check()?;
+    }
+    if let Some(duty_cycle) = config.analysis_duty_cycle.filter(|&duty_cycle| duty_cycle <= 0.0 || duty_cycle > 1.0) {
+        return Err(format!("analysis_duty_cycle {} is outside 0 (exclusive) to 1", duty_cycle).into());
+    }
+    Ok(())
+}
+
+// A config path may be a single config, a file holding an array of configs, or a directory of config files
+fn load_configs(config_path: &str) -> Result<Vec<Config>, Box<dyn std::error::Error>> {
+    if std::path::Path::new(config_path).is_dir() {
+        let mut paths = std::fs::read_dir(config_path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+        paths.sort();
+
+        return paths.iter()
+            .map(|path| load_config(&path.to_string_lossy()))
+            .collect();
+    }
+
+    let file = File::open(config_path)?;
+    let value: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(file))?;
+    match value {
+        serde_json::Value::Array(configs) => configs.into_iter()
+            .map(|config| serde_json::from_value(config).map_err(Into::into))
+            .collect(),
+        config => Ok(vec![serde_json::from_value(config)?]),
+    }
+}
+
+// An opened HackRF kept between captures so it isn't re-opened for every one, along with the
+// settings last applied to it so they are only sent again when they change
+#[derive(Default)]
+pub struct RadioSlot {
+    device: Option<HackRfOne<UnknownMode>>,
+    applied: Option<(u64, u32, Gains)>,
+    // Time spent opening and configuring the radio for the last capture
+    last_setup: Duration,
+}
+
+impl RadioSlot {
+    /// Settings sent to the radio for the last capture, None before the first one.
+    fn radio_config(&self) -> Option<RadioConfig> {
+        self.applied.map(|(frequency, sample_rate, gains)| RadioConfig::new(frequency, sample_rate, gains))
+    }
+}
+
+// The settings as the hardware receives them. The HackRF can't report its settings back, so this
+// records what was sent after the same snapping the driver and firmware apply: gains are rounded
+// down to their step and the baseband filter follows the sample rate.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct RadioConfig {
+    frequency_hz: u64,
+    sample_rate_hz: u32,
+    sample_rate_divider: u32,
+    // Requested from the firmware, which uses its nearest supported filter setting
+    baseband_filter_hz: u32,
+    lna_gain_db: u16,
+    vga_gain_db: u16,
+    amp_enable: bool,
+    // Antenna port power, the bias-tee
+    antenna_power: bool,
+}
+
+impl RadioConfig {
+    fn new(frequency: u64, sample_rate: u32, gains: Gains) -> Self {
+        RadioConfig {
+            frequency_hz: frequency,
+            sample_rate_hz: sample_rate,
+            sample_rate_divider: 1,
+            // set_sample_rate sets the filter to 75% of the sample rate
+            baseband_filter_hz: (0.75 * sample_rate as f32) as u32,
+            lna_gain_db: gains.lna & !0x07,
+            vga_gain_db: gains.vga & !0x01,
+            amp_enable: gains.amp,
+            antenna_power: gains.antenna_power,
+        }
+    }
+}
+
+// An unopened slot is filled by opening the first HackRF found; not finding one is a no_device error
+fn take_or_open(slot: &mut RadioSlot) -> Result<HackRfOne<UnknownMode>, hackrfone::Error> {
+    match slot.device.take() {
+        Some(radio) => Ok(radio),
+        None => {
+            slot.applied = None;
+            HackRfOne::new().ok_or(hackrfone::Error::Usb(hackrfone::rusb::Error::NoDevice))
+        }
+    }
+}
+
+fn scan_freq(slot: &mut RadioSlot, frequency: u64, sample_rate: u32, gains: Gains, duration: Duration) -> Result<Vec<u8>, hackrfone::Error> {
+    let setup_start = Instant::now();
+    let mut radio = take_or_open(slot)?;
+    let settings = (frequency, sample_rate, gains);
+    if slot.applied != Some(settings) {
+        // Cleared first so a failure part way through forces a full reconfigure next time
+        slot.applied = None;
+        radio.set_freq(frequency)?;
+        radio.set_sample_rate(sample_rate, 1)?;
+        radio.set_amp_enable(gains.amp)?;
+        radio.set_antenna_enable(gains.antenna_power.into())?;
+        radio.set_lna_gain(gains.lna)?;
+        radio.set_vga_gain(gains.vga)?;
+        slot.applied = Some(settings);
+    }
 
     // Enter RX mode and receive samples
-    let mut radio_rx = radio.into_rx_mode().expect("Failed to enter RX mode");
+    let mut radio_rx = radio.into_rx_mode()?;
+    slot.last_setup = setup_start.elapsed();
 
     let start_time = Instant::now();
     let mut raw_samples = Vec::new();
 
     loop {
-        let samples = radio_rx.rx().expect("Failed to receive samples");
+        let samples = radio_rx.rx()?;
         raw_samples.extend(samples);
 
         if start_time.elapsed() >= duration {
@@ -50,82 +700,494 @@ fn scan_freq(mut radio: HackRfOne<UnknownMode>, frequency: u64, sample_rate: u32
         }
     }
 
-    raw_samples
+    slot.device = Some(radio_rx.stop_rx()?);
 
+    Ok(raw_samples)
 }
 
+// Captures already leave RX mode when they finish. Idling also switches off the amp and antenna
+// power, and forgets the applied settings so the next capture re-arms the radio in full.
+fn idle_radio(slot: &mut RadioSlot) -> Result<(), hackrfone::Error> {
+    let Some(radio) = slot.device.as_mut() else {
+        return Ok(());
+    };
+    slot.applied = None;
+    radio.set_amp_enable(false)?;
+    radio.set_antenna_enable(0)?;
+    scan_println!("Radio idle: RX stopped, amp and antenna power off");
+    Ok(())
+}
 
-fn analyze_samples(samples: Vec<u8>) -> Vec<f64> {
-    samples.iter().map(|&sample| {
-        let sample_f64 = sample as f64;
-        if sample_f64 > 0.0 {
-            20.0 * sample_f64.log10()
-        } else {
-            0.0
+// Capture a window, handling radio errors as configured in radio_error_actions.
+// Returns None when the window was skipped.
+fn capture(config: &Config, slot: &mut RadioSlot, frequency: u64, sample_rate: u32, gains: Gains, duration: Duration) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    capture_logged(config, slot, frequency, sample_rate, gains, duration, &mut |message| scan_println!("{}", message))
+}
+
+// capture, with retries and skipped windows reported to `log` instead of stdout
+fn capture_logged(
+    config: &Config,
+    slot: &mut RadioSlot,
+    frequency: u64,
+    sample_rate: u32,
+    gains: Gains,
+    duration: Duration,
+    log: &mut dyn FnMut(String),
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let mut retries = 0;
+
+    loop {
+        let error = match scan_freq(slot, frequency, sample_rate, gains, duration) {
+            Ok(raw_samples) => {
+                budget::record(raw_samples.len());
+                return Ok(Some(raw_samples));
+            }
+            Err(error) => error,
+        };
+
+        let kind = radio_errors::error_kind(&error);
+        match radio_errors::action_for(&config.radio_error_actions, &error) {
+            ErrorAction::Retry if retries < config.radio_error_max_retries => {
+                retries += 1;
+                log(format!("Radio error ({}): {}, retrying ({} of {})", kind, error, retries, config.radio_error_max_retries));
+            }
+            ErrorAction::Retry | ErrorAction::SkipWindow => {
+                log(format!("Radio error ({}): {}, skipping window", kind, error));
+                return Ok(None);
+            }
+            ErrorAction::Abort => {
+                log(format!("Radio error ({}): {}, aborting", kind, error));
+                return Err(error.into());
+            }
+        }
+    }
+}
+
+
+// Read fresh for every result so the values stay current. A missing or malformed file only
+// drops the context from the result.
+fn read_context(config: &Config) -> Option<serde_json::Map<String, serde_json::Value>> {
+    let path = config.context_path.as_ref()?;
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            scan_println!("Warning: failed to read context file {}: {}", path, e);
+            return None;
         }
-    }).collect()
+    };
+    match serde_json::from_str(&contents) {
+        Ok(serde_json::Value::Object(context)) => Some(context),
+        Ok(_) => {
+            scan_println!("Warning: context file {} is not a JSON object", path);
+            None
+        }
+        Err(e) => {
+            scan_println!("Warning: failed to parse context file {}: {}", path, e);
+            None
+        }
+    }
+}
+
+// z for the 95% noise floor confidence interval
+const NOISE_FLOOR_CI_Z: f64 = 1.96;
+
+// dB value of a full-scale sample in analyze_samples
+const FULL_SCALE_DB: f64 = 48.130_803_608_679_1; // 20 * log10(255)
+
+fn strength_dbfs(strength: f64) -> f64 {
+    strength - FULL_SCALE_DB
+}
+
+// Weakest signal, in dBm at the antenna port, that the detector flags. A window is a detection
+// when its strength exceeds detection_threshold, or with rise detection when it rises delta_db
+// above the baseline, which on an idle channel sits at the noise floor. That strength in dBFS
+// plus calibration_offset_db, the dBm of a full-scale sample at the configured gains and sample
+// rate, is the sensitivity. None without calibration, or a noise floor for rise detection.
+fn min_detectable_dbm(config: &Config, rise_detection: Option<&RiseDetection>, noise_floor: Option<f64>) -> Option<f64> {
+    let offset = config.calibration_offset_db?;
+    let level = match rise_detection {
+        Some(rise) => noise_floor? + rise.delta_db,
+        None => config.detection_threshold,
+    };
+    Some(strength_dbfs(level) + offset)
+}
+
+
+
+
+// One-line summary of a result for syslog
+fn summary_message(data: &SignalData) -> String {
+    format!(
+        "frequency={} detected={} max_strength={:.1} durations=\"{}\"",
+        data.frequency, data.is_signal_detected, data.max_signal_strength, data.zwave_durations,
+    )
+}
+
+// Estimate a transmitter's repeat period from the start times of its transmissions.
+// Gaps between consecutive starts are binned to whole seconds and the most common gap wins;
+// the estimate is the mean of gaps within a second of it, and the confidence is the share of
+// all gaps that fall there. Returns (period, confidence).
+fn estimate_period(starts: &[u64], min_detections: usize) -> Option<(f64, f64)> {
+    if starts.len() < min_detections.max(2) {
+        return None;
+    }
+
+    let gaps: Vec<u64> = starts.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for &gap in &gaps {
+        *counts.entry(gap).or_default() += 1;
+    }
+
+    // Prefer the shorter gap on ties so the result doesn't depend on HashMap order
+    let (&mode, _) = counts.iter().max_by_key(|&(&gap, &count)| (count, std::cmp::Reverse(gap)))?;
+    let near_mode: Vec<u64> = gaps.iter().copied().filter(|gap| gap.abs_diff(mode) <= 1).collect();
+
+    let period = near_mode.iter().sum::<u64>() as f64 / near_mode.len() as f64;
+    let confidence = near_mode.len() as f64 / gaps.len() as f64;
+    Some((period, confidence))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = load_config("config.json")?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = run(&args).await;
+    on_detect::wait_for_commands();
+    result
+}
 
-    if config.instant_scan {
-        run_instant_scan().await?;
+async fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if args.iter().any(|arg| arg == "--version" || arg == "-V") {
+        println!("{}", version_string());
+        return Ok(());
+    }
+
+    if args.first().map(String::as_str) == Some("decrypt") {
+        return run_decrypt(&args[1..]);
+    }
+
+    if args.first().map(String::as_str) == Some("analyze") {
+        return run_analyze(&args[1..]);
+    }
+
+    let config_path = flag_value(args, "--config")?.unwrap_or("config.json");
+
+    let configs = load_configs(config_path)?;
+    if configs.is_empty() {
+        return Err(format!("No configs found in {}", config_path).into());
+    }
+
+    // Resolve every config up front so a bad entry fails before any scanning starts
+    let gains = configs.iter().map(resolve_gains).collect::<Result<Vec<_>, _>>()?;
+    configs.iter().try_for_each(check_config)?;
+    let mut radio = RadioSlot::default();
+    shutdown::install(Duration::from_secs(configs[0].shutdown_grace_secs));
+    // The budget covers the whole session, so batch runs take it from the first config
+    budget::set_limit(configs[0].scan_budget_bytes);
+
+    if args.first().map(String::as_str) == Some("ab-test") {
+        return ab_test::run_ab_test(&configs[0], &mut radio).await;
+    }
+
+    if args.first().map(String::as_str) == Some("compare") {
+        return compare::run_compare(&configs[0], gains[0], &mut radio).await;
+    }
+
+    if args.first().map(String::as_str) == Some("history") {
+        return history::run_history(&configs[0], &args[1..]);
+    }
+
+    if args.first().map(String::as_str) == Some("survey") {
+        return survey::run_survey(&configs[0], gains[0], &mut radio, &args[1..]).await;
+    }
+
+    if args.iter().any(|arg| arg == "--tui") {
+        #[cfg(feature = "tui")]
+        return tui::run_tui(&configs[0], gains[0], &mut radio);
+        #[cfg(not(feature = "tui"))]
+        return Err("--tui needs a build with the tui feature (cargo build --features tui)".into());
+    }
+
+    if args.iter().any(|arg| arg == "--placement") {
+        return placement::run_placement(&configs[0], gains[0], &mut radio).await;
+    }
+
+    if configs.len() == 1 {
+        run_config(&configs[0], gains[0], &mut radio, None).await?;
     } else {
-        run_scan_over_duration(config.start_after_duration, config.scan_duration).await?;
+        for (index, (config, &gains)) in configs.iter().zip(&gains).enumerate() {
+            if shutdown::requested() {
+                println!("Skipping remaining {} configs after shutdown request", configs.len() - index);
+                break;
+            }
+            if budget::spent() {
+                println!("Skipping remaining {} configs", configs.len() - index);
+                break;
+            }
+            println!("Running config {} of {}...", index + 1, configs.len());
+            run_config(config, gains, &mut radio, Some(index + 1)).await?;
+        }
+        println!("Completed {} configs", configs.len());
+    }
+
+    if let Some(budget) = budget::limit() {
+        println!("Captured {} of {} budgeted bytes", budget::captured(), budget);
     }
 
     Ok(())
 }
 
-pub async fn run_instant_scan() -> Result<bool, Box<dyn std::error::Error>>  {
-    println!("Running instant scan...");
+// Batch runs write one result file per config, suffixed with the config's position
+async fn run_config(config: &Config, gains: Gains, radio: &mut RadioSlot, batch_index: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let output_name = if config.instant_scan { "zwave_instantdata" } else { "zwave_scheduledata" };
+    let output_path = match batch_index {
+        Some(index) => format!("{}_{}.{}", output_name, index, config.output_format.extension()),
+        None => format!("{}.{}", output_name, config.output_format.extension()),
+    };
+
+    if let Some(count) = config.repeat_count.filter(|_| config.instant_scan) {
+        run_repeated_instant_scans(config, gains, radio, &output_path, count).await?;
+    } else {
+        scan_id::start(config.scan_uuid);
+        let output_path = scan_id::artifact_path(&output_path);
+        if config.instant_scan {
+            run_instant_scan(config, gains, radio, &output_path).await?;
+        } else {
+            run_scan_over_duration(config, gains, radio, &output_path).await?;
+        }
+    }
 
-    // define the 2 frequancy for EU Z-Wave
-    let frequency = 868_400_000u64; // 868.4 MHz
+    if config.idle_between_scans {
+        idle_radio(radio)?;
+    }
 
-    // define the bandwidth and sample rate for each scan
-    let sample_rate = 10_000_000u32; // 10 MS/s
+    Ok(())
+}
 
-    // define the duration for each scan
-    let duration = Duration::from_secs(5); // total of 20 seconds for each scan
+// Instant scans back to back on the same open radio. Only the first one pays for opening and
+// configuring the device, the rest just restart RX. Each scan gets its own scan ID and result
+// file, suffixed with its iteration, e.g. zwave_instantdata_repeat2.json.
+async fn run_repeated_instant_scans(config: &Config, gains: Gains, radio: &mut RadioSlot, output_path: &str, count: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut setup_times = Vec::new();
+    let (stem, extension) = output_path.rsplit_once('.').unwrap_or((output_path, "json"));
 
-    let radio: HackRfOne<UnknownMode> = HackRfOne::new().expect("Failed to open HackRF One");
-    let raw_samples: Vec<u8> = scan_freq(radio, frequency, sample_rate, duration);
+    for iteration in 1..=count {
+        if shutdown::requested() {
+            break;
+        }
+        if budget::spent() {
+            println!("Skipping remaining {} scans", count - iteration + 1);
+            break;
+        }
+        scan_id::start(config.scan_uuid);
+        let iteration_path = scan_id::artifact_path(&format!("{}_repeat{}.{}", stem, iteration, extension));
+        scan_println!("Instant scan {} of {}", iteration, count);
+        run_instant_scan(config, gains, radio, &iteration_path).await?;
+        setup_times.push(radio.last_setup);
+
+        if iteration < count {
+            if config.idle_between_scans && config.repeat_interval_secs > 0 {
+                idle_radio(radio)?;
+            }
+            sleep(Duration::from_secs(config.repeat_interval_secs)).await;
+        }
+    }
+
+    if let [first, rest @ ..] = setup_times.as_slice() {
+        if !rest.is_empty() {
+            let reused = rest.iter().sum::<Duration>() / rest.len() as u32;
+            scan_println!(
+                "Radio setup took {} ms on the first scan and {} ms on average after, saving about {} ms per scan",
+                first.as_millis(),
+                reused.as_millis(),
+                first.saturating_sub(reused).as_millis(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Value following a command-line flag, e.g. the path in `--config path`
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, Box<dyn std::error::Error>> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => match args.get(index + 1) {
+            Some(value) => Ok(Some(value.as_str())),
+            None => Err(format!("{} requires a value", flag).into()),
+        },
+        None => Ok(None),
+    }
+}
+
+// zwave_module analyze --stdin --sample-rate <hz> --frequency <hz> [--config <path>]
+// Raw samples carry no metadata, so the sample rate and frequency must be given explicitly.
+// The stream is analyzed in one-second windows until EOF. The config is optional, as the host
+// analyzing the stream may have none; without --config, config.json is used if it exists.
+fn run_analyze(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "Usage: zwave_module analyze --stdin --sample-rate <hz> --frequency <hz> [--config <path>]";
+
+    if !args.iter().any(|arg| arg == "--stdin") {
+        return Err(USAGE.into());
+    }
+    let sample_rate: u64 = flag_value(args, "--sample-rate")?.ok_or(USAGE)?.parse()?;
+    let frequency: u64 = flag_value(args, "--frequency")?.ok_or(USAGE)?.parse()?;
+    if sample_rate == 0 {
+        return Err("--sample-rate must be greater than zero".into());
+    }
+
+    let config = match flag_value(args, "--config")? {
+        Some(config_path) => load_config(config_path)?,
+        None if std::path::Path::new("config.json").exists() => load_config("config.json")?,
+        None => serde_json::from_value(serde_json::json!({
+            "instant_scan": false,
+            "start_after_duration": 0,
+            "scan_duration": 0,
+        }))?,
+    };
+    // The samples were captured by another radio, so the amp and LNA check for this one doesn't apply
+    let gains = Gains {
+        lna: resolve_gain("LNA", config.lna_gain, LNA_GAIN_MAX, LNA_GAIN_STEP, config.gain_clamp)?,
+        vga: resolve_gain("VGA", config.vga_gain, VGA_GAIN_MAX, VGA_GAIN_STEP, config.gain_clamp)?,
+        amp: config.amp_enable,
+        antenna_power: config.antenna_power,
+    };
+    check_config(&config)?;
+    shutdown::install(Duration::from_secs(config.shutdown_grace_secs));
+
+    scan_id::start(config.scan_uuid);
+    let bytes_per_second = sample_rate * source::BYTES_PER_SAMPLE;
+    let mut source = StdinSource::new(bytes_per_second as usize);
+    let mut scan = WindowedScan::new(&config, gains, frequency, sample_rate);
+    let mut bytes_read = 0u64;
+
+    scan_println!("Analyzing samples from stdin...");
+    while let Some(raw_samples) = source.next_window()? {
+        if shutdown::requested() {
+            break;
+        }
+        let window_start = Duration::from_secs_f64(bytes_read as f64 / bytes_per_second as f64);
+        bytes_read += raw_samples.len() as u64;
+        let window_end = Duration::from_secs_f64(bytes_read as f64 / bytes_per_second as f64);
+        scan.process_window(window_start, window_end, raw_samples);
+    }
+    scan_println!("Analyzed {:.1} seconds of samples", bytes_read as f64 / bytes_per_second as f64);
+
+    scan.finish(&scan_id::artifact_path(&format!("zwave_analyzedata.{}", config.output_format.extension())))
+}
+
+// zwave_module decrypt <file> [--identity <identity file>]
+fn run_decrypt(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = None;
+    let mut identity_path = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--identity" {
+            identity_path = Some(args.next().ok_or("--identity requires a file path")?.as_str());
+        } else {
+            path = Some(arg.as_str());
+        }
+    }
+
+    let path = path.ok_or("Usage: zwave_module decrypt <file> [--identity <identity file>]")?;
+    encryption::decrypt_artifact(path, identity_path, &mut std::io::stdout().lock())
+}
+
+pub async fn run_instant_scan(config: &Config, gains: Gains, radio: &mut RadioSlot, output_path: &str) -> Result<bool, Box<dyn std::error::Error>>  {
+    scan_println!("Running instant scan...");
+
+    let frequency = config.frequency;
+    let sample_rate = config.sample_rate;
+    let duration = Duration::from_secs(config.instant_duration);
+
+    let raw_samples: Vec<u8> = capture(config, radio, frequency, sample_rate, gains, duration)?.unwrap_or_default();
 
     
     // Print the number of samples received
-    println!("Received {} samples", raw_samples.len());
+    scan_println!("Received {} samples", raw_samples.len());
 
-    let signal_strengths_db = analyze_samples(raw_samples);
-
-    let max_strength = signal_strengths_db.iter().max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    if is_clipped(&raw_samples) {
+        warn_clipped(config, gains);
+    }
+    let histogram = byte_histogram(&raw_samples);
+    let noise_floor = estimate_noise_floor(&histogram);
+    let noise_floor_ci = noise_floor_interval(&histogram, NOISE_FLOOR_CI_Z).filter(|_| config.emit_noise_floor_ci);
+    let raw_max = raw_samples.iter().max().copied();
+    // Instant scans always use the fixed threshold
+    let detection = Detector::new(DetectorSettings { rise_detection: None, ..config.detector_settings() }).process(&raw_samples);
+    let idle = detection.idle;
+    let max_strength = detection.strength;
 
     match max_strength {
-        Some(max) => println!("The highest strength found is: {}", max),
-        None => println!("The vector is empty"),
+        Some(max) => scan_println!("The highest strength found is: {}", max),
+        None if idle => scan_println!("Capture is below the energy gate, skipped analysis"),
+        None => scan_println!("The vector is empty"),
     }
     
-    if max_strength > Some(&50.0) {
-        println!("Z-Wave signal detected");
+    if let Some(max) = max_strength.filter(|_| detection.detected) {
+        scan_println!("Z-Wave signal detected");
+        if let Some(on_detect) = OnDetect::new(config) {
+            on_detect.detected(frequency, max);
+        }
     } else {
-        println!("No Z-Wave signal detected");
+        scan_println!("No Z-Wave signal detected");
     }
 
     let data = SignalData {
         frequency: frequency as f64,
-        is_signal_detected: max_strength.map_or(false, |&strength| strength > 50.0),
-        max_signal_strength: *max_strength.unwrap_or(&0.0),
-        zwave_durations: String::from("5"),
+        is_signal_detected: detection.detected,
+        max_signal_strength: max_strength.unwrap_or(0.0),
+        raw_strength_dbfs: max_strength.map(strength_dbfs),
+        calibrated_dbm: max_strength.zip(config.calibration_offset_db).map(|(max, offset)| strength_dbfs(max) + offset),
+        min_detectable_dbm: min_detectable_dbm(config, None, noise_floor),
+        zwave_durations: config.instant_duration.to_string(),
+        dynamic_range_db: if config.emit_dynamic_range {
+            max_strength.zip(noise_floor).map(|(max, floor)| max - floor)
+        } else {
+            None
+        },
+        noise_floor_db: noise_floor.filter(|_| config.emit_noise_floor_ci),
+        noise_floor_ci_low: noise_floor_ci.map(|(low, _)| low),
+        noise_floor_ci_high: noise_floor_ci.map(|(_, high)| high),
+        raw_max_sample: raw_max.filter(|_| config.debug_raw_max),
+        high_gain_with_amp: high_gain_with_amp(config, gains.lna).then_some(true),
+        receive_safe: config.receive_safe.then_some(true),
+        scan_id: scan_id::current(),
+        radio_config: radio.radio_config(),
+        context: read_context(config),
+        energy_gate: config.energy_gate_db.map(|_| EnergyGateSummary::new(1, idle as usize)),
+        ..Default::default()
     };
 
-    let json = serde_json::to_string(&data).expect("Failed to serialize data");
-    println!("{}", json);
-    
-    let mut file = File::create("zwave_instantdata.json").expect("Failed to create file");
-    file.write_all(json.as_bytes()).expect("Failed to write data");
+    if let Some(presence_config) = config.presence_score.as_ref() {
+        let duty_cycle = if data.is_signal_detected { 1.0 } else { 0.0 };
+        let peak_snr = max_strength.zip(noise_floor).map_or(0.0, |(max, floor)| max - floor);
+        let score = presence::score(presence_config, duty_cycle, peak_snr);
+        scan_println!("Presence score: {:.0}", score);
+        presence::write_score(config, score)?;
+    }
+
+    let json = output_format::render(&data, config.output_format, false).expect("Failed to serialize data");
 
+    let mut syslog = config.syslog.as_ref().map(SyslogOutput::connect);
+    if let Some(syslog) = syslog.as_mut() {
+        if data.is_signal_detected {
+            syslog.send(&format!("Z-Wave signal detected strength={:.1}", data.max_signal_strength));
+        }
+        syslog.send(&summary_message(&data));
+    }
+
+    if !config.syslog.as_ref().is_some_and(|syslog| syslog.exclusive) {
+        println!("{}", json);
+        encryption::write_artifact(config.encryption.as_ref(), output_path, json.as_bytes())?;
+    }
+
+    if let Some(path) = config.fifo_path.as_ref() {
+        fifo_output::send(path, &serde_json::to_string(&data)?);
+    }
+
+    if let Some((history, floor)) = config.noise_history.as_ref().zip(noise_floor) {
+        history::record(history, config.encryption.as_ref(), frequency, floor)?;
+    }
 
     if json == "{}" {
         Ok(false)
@@ -134,81 +1196,53 @@ pub async fn run_instant_scan() -> Result<bool, Box<dyn std::error::Error>>  {
     }
 }
 
-async fn run_scan_over_duration(start_after_duration: u64, scan_duration: u64) -> Result<(), Box<dyn std::error::Error>> {
-    for i in (1..=start_after_duration).rev() {
-        println!("Scan starts in {} seconds", i);
+async fn run_scan_over_duration(config: &Config, gains: Gains, radio: &mut RadioSlot, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    // Guards extend the capture around the scan period, the leading one eating into the countdown.
+    // It can't start before the scan does, so it is at most start_after_duration long.
+    let leading_guard = config.leading_guard_secs.min(config.start_after_duration);
+    if leading_guard < config.leading_guard_secs {
+        scan_println!("Leading guard shortened to {} s, the time before the scan starts", leading_guard);
+    }
+    let scan_duration = leading_guard + config.scan_duration + config.trailing_guard_secs;
+    for i in (1..=config.start_after_duration - leading_guard).rev() {
+        if shutdown::requested() {
+            break;
+        }
+        scan_println!("Scan starts in {} seconds", i);
         sleep(Duration::from_secs(1)).await;
     }
 
-    println!("Starting scan for {} seconds...", scan_duration);
+    scan_println!("Starting scan for {} seconds...", scan_duration);
 
-    let frequency = 868_400_000u64;
-    let sample_rate = 10_000_000u32;
+    let frequency = config.frequency;
+    let sample_rate = config.sample_rate;
     let scan_start_time = Instant::now();
-    let mut intervals = Vec::new();
-    let mut max_strength = 0.0_f64;
-    let mut signal_detected = false;
+    let mut scan = WindowedScan::new(config, gains, frequency, sample_rate as u64);
+    if scan_duration != config.scan_duration {
+        scan_println!("Including {} s leading and {} s trailing guard", leading_guard, config.trailing_guard_secs);
+        scan.set_guards(Duration::from_secs(leading_guard), Duration::from_secs(leading_guard + config.scan_duration));
+    }
 
     while Instant::now().duration_since(scan_start_time) < Duration::from_secs(scan_duration) {
+        if shutdown::requested() {
+            scan_println!("Stopping scan early after {} seconds", scan_start_time.elapsed().as_secs());
+            break;
+        }
         if Instant::now().duration_since(scan_start_time) + Duration::from_secs(1) > Duration::from_secs(scan_duration) {
             // If the remaining time is less than 1 second, break the loop
             break;
         }
 
-        let radio = HackRfOne::new().expect("Failed to open HackRF One");
-        let raw_samples = scan_freq(radio, frequency, sample_rate, Duration::from_secs(1));
-        let signal_strengths = analyze_samples(raw_samples);
-
-        if let Some(&strength) = signal_strengths.iter().max_by(|a, b| a.partial_cmp(b).unwrap()) {
-            if strength > 50.0 { // Threshold for signal detection
-                signal_detected = true;
-                max_strength = max_strength.max(strength);
-                let elapsed = Instant::now().duration_since(scan_start_time).as_secs();
-                intervals.push((elapsed, elapsed + 1));
-            }
-        }
-    }
-
-    let merged_intervals = merge_intervals(intervals);
-    let durations_str = merged_intervals.iter()
-        .map(|&(start, end)| format!("{}-{}", start, end))
-        .collect::<Vec<_>>()
-        .join(",");
-
-    let result = SignalData {
-        frequency: frequency as f64 / 1_000_000.0,
-        is_signal_detected: signal_detected,
-        max_signal_strength: max_strength,
-        zwave_durations: durations_str,
-    };
-
-    let json = serde_json::to_string_pretty(&result)?;
-    println!("{}", json);
-
-    let mut file = File::create("zwave_scheduledata.json")?;
-    file.write_all(json.as_bytes())?;
-
-    Ok(())
-}
-
-fn merge_intervals(mut intervals: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
-    if intervals.is_empty() {
-        return Vec::new();
+        let window_start = scan_start_time.elapsed();
+        let Some(raw_samples) = capture(config, radio, frequency, sample_rate, gains, Duration::from_secs(1))? else {
+            scan.skip_window();
+            continue;
+        };
+        scan.process_window(window_start, scan_start_time.elapsed(), raw_samples);
     }
 
-    intervals.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-    let mut merged = vec![intervals[0]];
-
-    for &(start, end) in &intervals[1..] {
-        let last = merged.last_mut().unwrap();
-
-        // Fusionnez si l'intervalle de départ est dans les 5 secondes suivant la fin du dernier intervalle fusionné
-        if start <= last.1 + 5 {
-            last.1 = last.1.max(end);
-        } else {
-            merged.push((start, end));
-        }
+    if let Some(radio_config) = radio.radio_config() {
+        scan.set_radio_config(radio_config);
     }
-
-    merged
+    scan.finish(output_path)
 }
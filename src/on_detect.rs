@@ -0,0 +1,94 @@
+// Runs the configured on_detect_command when a signal is detected, as a generic hook for local
+// automation. Detection details are passed in environment variables:
+//
+//   ZWAVE_FREQUENCY_HZ  frequency the detection was made on
+//   ZWAVE_STRENGTH      peak strength of the detection in dB
+//   ZWAVE_TIMESTAMP     unix time of the detection in seconds
+//   ZWAVE_SCAN_ID       scan ID, when scan_uuid is enabled
+//
+// The command runs through `sh -c` without blocking the scan, and its exit status is logged once
+// it finishes. Before the process exits it waits up to EXIT_WAIT for commands still running, so
+// their status is logged after short runs too. A failing command never stops the scan. The cooldown covers the whole session, so
+// repeated scans and batch configs don't each run the command for the same persistent signal.
+
+use crate::{scan_id, Config};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static LAST_RUN: Mutex<Option<Instant>> = Mutex::new(None);
+// Threads waiting on running commands to log their exit status
+static WAITERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+const EXIT_WAIT: Duration = Duration::from_secs(10);
+
+pub struct OnDetect {
+    command: String,
+    cooldown: Duration,
+}
+
+impl OnDetect {
+    pub fn new(config: &Config) -> Option<Self> {
+        config.on_detect_command.as_ref().map(|command| OnDetect {
+            command: command.clone(),
+            cooldown: Duration::from_secs(config.on_detect_cooldown_secs),
+        })
+    }
+
+    /// Run the command for a detection, unless it already ran within the cooldown in this session.
+    pub fn detected(&self, frequency: u64, strength: f64) {
+        {
+            let mut last_run = LAST_RUN.lock().unwrap_or_else(|e| e.into_inner());
+            if last_run.is_some_and(|last_run| last_run.elapsed() < self.cooldown) {
+                return;
+            }
+            *last_run = Some(Instant::now());
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&self.command)
+            .env("ZWAVE_FREQUENCY_HZ", frequency.to_string())
+            .env("ZWAVE_STRENGTH", format!("{:.1}", strength))
+            .env("ZWAVE_TIMESTAMP", timestamp.to_string());
+        if let Some(id) = scan_id::current() {
+            command.env("ZWAVE_SCAN_ID", id);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                scan_println!("Failed to run on_detect_command: {}", e);
+                return;
+            }
+        };
+        let prefix = scan_id::log_prefix();
+        let waiter = std::thread::spawn(move || match child.wait() {
+            Ok(status) if status.success() => println!("{}on_detect_command finished: {}", prefix, status),
+            Ok(status) => println!("{}on_detect_command failed: {}", prefix, status),
+            Err(e) => println!("{}Failed to wait for on_detect_command: {}", prefix, e),
+        });
+        let mut waiters = WAITERS.lock().unwrap_or_else(|e| e.into_inner());
+        waiters.retain(|waiter| !waiter.is_finished());
+        waiters.push(waiter);
+    }
+}
+
+/// Wait up to EXIT_WAIT for commands that are still running, so their exit status gets logged.
+pub fn wait_for_commands() {
+    let deadline = Instant::now() + EXIT_WAIT;
+    loop {
+        let mut waiters = WAITERS.lock().unwrap_or_else(|e| e.into_inner());
+        waiters.retain(|waiter| !waiter.is_finished());
+        if waiters.is_empty() {
+            return;
+        }
+        if Instant::now() >= deadline {
+            println!("{} on_detect_command runs still going after {} seconds, exiting without their status", waiters.len(), EXIT_WAIT.as_secs());
+            return;
+        }
+        drop(waiters);
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
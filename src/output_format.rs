@@ -0,0 +1,66 @@
+// Result formats. JSON is the default; logfmt writes the same fields as one line of key=value
+// pairs for log collectors that can't parse JSON. Nested objects are flattened into dotted keys
+// (e.g. radio_config.lna_gain_db=16), and arrays into keys indexed by position (e.g.
+// timeline.0.active=true). Null values and empty arrays are written as an empty value.
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Logfmt,
+}
+
+impl OutputFormat {
+    /// File extension for a result in this format, so logfmt files aren't mistaken for JSON.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Logfmt => "log",
+        }
+    }
+}
+
+/// Render a result in `format`, with JSON pretty-printed when `pretty` is set.
+pub fn render<T: Serialize>(value: &T, format: OutputFormat, pretty: bool) -> Result<String, serde_json::Error> {
+    match format {
+        OutputFormat::Json if pretty => serde_json::to_string_pretty(value),
+        OutputFormat::Json => serde_json::to_string(value),
+        OutputFormat::Logfmt => {
+            let mut pairs = Vec::new();
+            flatten("", &serde_json::to_value(value)?, &mut pairs);
+            Ok(pairs.join(" "))
+        }
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, pairs: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(&key, field, pairs);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (index, item) in items.iter().enumerate() {
+                flatten(&format!("{}.{}", prefix, index), item, pairs);
+            }
+        }
+        Value::String(text) => pairs.push(format!("{}={}", prefix, quote(text))),
+        Value::Null | Value::Array(_) => pairs.push(format!("{}=", prefix)),
+        other => pairs.push(format!("{}={}", prefix, quote(&other.to_string()))),
+    }
+}
+
+// Values with spaces, quotes, '=' or nothing at all are quoted, escaping quotes and backslashes
+fn quote(text: &str) -> String {
+    if !text.is_empty() && !text.contains([' ', '"', '=', '\\', '\n']) {
+        return text.to_string();
+    }
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
@@ -0,0 +1,77 @@
+use crate::{analyze_samples, byte_histogram, capture, estimate_noise_floor, shutdown, Config, Gains, RadioSlot};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const BAR_WIDTH: usize = 40;
+// Strength that fills the whole bar, the top of the 8-bit dB range
+const BAR_FULL_SCALE_DB: f64 = 48.0;
+
+struct Reading {
+    strength: f64,
+    snr: f64,
+}
+
+/// Antenna placement assistant: capture short windows back to back and show a live readout
+/// until Ctrl-C, then summarize the levels seen and when the best one occurred.
+pub async fn run_placement(config: &Config, gains: Gains, radio: &mut RadioSlot) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Placement mode on {:.1} MHz, press Ctrl-C to finish", config.frequency as f64 / 1_000_000.0);
+
+    let capture_duration = Duration::from_millis(config.placement_capture_ms);
+    let session_start = Instant::now();
+    let mut min_strength = f64::MAX;
+    let mut max_strength = f64::MIN;
+    let mut best: Option<(Reading, Duration)> = None;
+    let mut cycles_since_beep = 0usize;
+
+    while !shutdown::requested() {
+        let Some(raw_samples) = capture(config, radio, config.frequency, config.sample_rate, gains, capture_duration)? else {
+            continue;
+        };
+        let noise_floor = estimate_noise_floor(&byte_histogram(&raw_samples)).unwrap_or(0.0);
+        let strength = analyze_samples(raw_samples).into_iter().fold(0.0_f64, f64::max);
+        let reading = Reading { strength, snr: strength - noise_floor };
+
+        min_strength = min_strength.min(reading.strength);
+        max_strength = max_strength.max(reading.strength);
+
+        let filled = ((reading.strength / BAR_FULL_SCALE_DB).clamp(0.0, 1.0) * BAR_WIDTH as f64) as usize;
+        print!(
+            "\r  RSSI {:6.1} dB   SNR {:5.1} dB   [{:<width$}]",
+            reading.strength,
+            reading.snr,
+            "#".repeat(filled),
+            width = BAR_WIDTH,
+        );
+
+        // Beep more often as the SNR rises, from every 10th cycle down to every cycle
+        cycles_since_beep += 1;
+        let beep_period = (10.0 - reading.snr / 4.0).clamp(1.0, 10.0) as usize;
+        if config.placement_beep && cycles_since_beep >= beep_period {
+            print!("\x07");
+            cycles_since_beep = 0;
+        }
+        std::io::stdout().flush()?;
+
+        if best.as_ref().is_none_or(|(best_reading, _)| reading.snr > best_reading.snr) {
+            best = Some((reading, session_start.elapsed()));
+        }
+    }
+
+    println!();
+    match best {
+        Some((reading, at)) => {
+            println!("Placement summary over {:.1} s:", session_start.elapsed().as_secs_f64());
+            println!("  Min RSSI: {:.1} dB", min_strength);
+            println!("  Max RSSI: {:.1} dB", max_strength);
+            println!(
+                "  Best: RSSI {:.1} dB, SNR {:.1} dB at {:.1} s",
+                reading.strength,
+                reading.snr,
+                at.as_secs_f64(),
+            );
+        }
+        None => println!("No captures were taken"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+// Single-number channel occupancy for external pollers, written to its own small file after each
+// scan so a poller doesn't need to parse the full result.
+//
+// The score is a weighted average of two inputs, each scaled to 0..1, times 100:
+//
+//   score = 100 * (duty_cycle_weight * duty_cycle + snr_weight * min(peak_snr / snr_full_scale_db, 1))
+//               / (duty_cycle_weight + snr_weight)
+//
+// duty_cycle is the fraction of windows with a detection, peak_snr the highest window strength
+// above the noise floor in dB (negative SNR counts as zero).
+//
+// The file is always zwave_score.json, overwritten by each scan so pollers have a fixed path to
+// read; with scan_uuid the ID of the scan that wrote it is inside the file instead of its name.
+
+use crate::{encryption, scan_id, Config};
+use serde::{Serialize, Deserialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PresenceScoreConfig {
+    #[serde(default = "default_weight")]
+    pub duty_cycle_weight: f64,
+    #[serde(default = "default_weight")]
+    pub snr_weight: f64,
+    // Peak SNR that counts as full occupancy on the SNR side
+    #[serde(default = "default_snr_full_scale_db")]
+    pub snr_full_scale_db: f64,
+}
+
+fn default_weight() -> f64 {
+    0.5
+}
+
+fn default_snr_full_scale_db() -> f64 {
+    30.0
+}
+
+#[derive(Serialize)]
+struct PresenceScore {
+    score: f64,
+    timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scan_id: Option<String>,
+}
+
+/// Occupancy score from 0 to 100, see the formula above.
+pub fn score(config: &PresenceScoreConfig, duty_cycle: f64, peak_snr: f64) -> f64 {
+    let total_weight = config.duty_cycle_weight + config.snr_weight;
+    if total_weight <= 0.0 {
+        return 0.0;
+    }
+    let snr = if config.snr_full_scale_db > 0.0 {
+        (peak_snr / config.snr_full_scale_db).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    100.0 * (config.duty_cycle_weight * duty_cycle.clamp(0.0, 1.0) + config.snr_weight * snr) / total_weight
+}
+
+pub fn write_score(config: &Config, score: f64) -> Result<(), Box<dyn std::error::Error>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let json = serde_json::to_string(&PresenceScore { score, timestamp, scan_id: scan_id::current() })?;
+    encryption::write_artifact(config.encryption.as_ref(), "zwave_score.json", json.as_bytes())?;
+    Ok(())
+}
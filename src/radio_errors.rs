@@ -0,0 +1,49 @@
+use hackrfone::rusb;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+// What to do when a radio call fails during a capture
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorAction {
+    // Capture the same window again
+    Retry,
+    // Give up on this window and carry on with the scan
+    SkipWindow,
+    // Stop the scan with an error
+    Abort,
+}
+
+/// Short name for a radio error, used as the key in `radio_error_actions`.
+pub fn error_kind(error: &hackrfone::Error) -> &'static str {
+    match error {
+        hackrfone::Error::Usb(usb_error) => match usb_error {
+            rusb::Error::Timeout => "timeout",
+            rusb::Error::Interrupted => "interrupted",
+            rusb::Error::Overflow => "overflow",
+            rusb::Error::Pipe => "pipe",
+            rusb::Error::Io => "io",
+            rusb::Error::Busy => "busy",
+            rusb::Error::NoDevice => "no_device",
+            rusb::Error::Access => "access",
+            _ => "usb_other",
+        },
+        hackrfone::Error::CtrlTransfer { .. } => "ctrl_transfer",
+        hackrfone::Error::Version { .. } => "version",
+        hackrfone::Error::Argument => "argument",
+    }
+}
+
+fn default_action(kind: &str) -> ErrorAction {
+    match kind {
+        "timeout" | "interrupted" => ErrorAction::Retry,
+        "overflow" | "pipe" | "io" | "ctrl_transfer" => ErrorAction::SkipWindow,
+        _ => ErrorAction::Abort,
+    }
+}
+
+/// Action for an error, taking config overrides before the built-in defaults.
+pub fn action_for(overrides: &HashMap<String, ErrorAction>, error: &hackrfone::Error) -> ErrorAction {
+    let kind = error_kind(error);
+    overrides.get(kind).copied().unwrap_or_else(|| default_action(kind))
+}
@@ -0,0 +1,303 @@
+use crate::{byte_histogram, fifo_output, source, encryption, estimate_noise_floor, estimate_period, high_gain_with_amp, history, is_clipped};
+use crate::{min_detectable_dbm, noise_floor_interval, output_format, presence, read_context, scan_id, shutdown, step_script, strength_dbfs, summary_message, warn_clipped};
+use crate::{Config, EnergyGateSummary, Gains, GuardSummary, RadioConfig, RiseSummary, SignalData, ThresholdCount, ThresholdSweepConfig, ThrottleSummary, TimelineRun, NOISE_FLOOR_CI_Z};
+use crate::on_detect::OnDetect;
+use crate::step_script::StepBreakdown;
+use crate::syslog_output::SyslogOutput;
+use std::time::{Duration, Instant};
+use zwave_module::detector::Detector;
+use zwave_module::intervals::{format_intervals, format_intervals_capped, merge_intervals, run_lengths};
+
+/// Detection state for a scan made of consecutive windows, fed one window at a time.
+/// It doesn't need to know the scan length up front, so it works for timed captures and
+/// for streams that end whenever their input does.
+pub struct WindowedScan<'a> {
+    config: &'a Config,
+    gains: Gains,
+    frequency: u64,
+    sample_rate: u64,
+    intervals: Vec<(u64, u64)>,
+    max_strength: f64,
+    signal_detected: bool,
+    histogram: [u64; 256],
+    // Strongest window over all analyzed windows, detected or not
+    peak_strength: Option<f64>,
+    // None for a window that was skipped
+    raw_max_per_window: Vec<Option<u8>>,
+    sample_counts: Vec<u64>,
+    window_strengths: Vec<f64>,
+    window_states: Vec<bool>,
+    syslog: Option<SyslogOutput>,
+    on_detect: Option<OnDetect>,
+    step_breakdown: StepBreakdown,
+    first_detection: Option<f64>,
+    clipping_reported: bool,
+    radio_config: Option<RadioConfig>,
+    idle_windows: usize,
+    // Start and end of the main scan period when guard intervals surround it
+    guards: Option<(Duration, Duration)>,
+    leading_guard_intervals: Vec<(u64, u64)>,
+    trailing_guard_intervals: Vec<(u64, u64)>,
+    detector: Detector,
+    analysis_time: Duration,
+    throttle_time: Duration,
+}
+
+impl<'a> WindowedScan<'a> {
+    pub fn new(config: &'a Config, gains: Gains, frequency: u64, sample_rate: u64) -> Self {
+        WindowedScan {
+            config,
+            gains,
+            frequency,
+            sample_rate,
+            intervals: Vec::new(),
+            max_strength: 0.0,
+            signal_detected: false,
+            histogram: [0; 256],
+            peak_strength: None,
+            raw_max_per_window: Vec::new(),
+            sample_counts: Vec::new(),
+            window_strengths: Vec::new(),
+            window_states: Vec::new(),
+            syslog: config.syslog.as_ref().map(SyslogOutput::connect),
+            on_detect: OnDetect::new(config),
+            step_breakdown: StepBreakdown::default(),
+            first_detection: None,
+            clipping_reported: false,
+            radio_config: None,
+            idle_windows: 0,
+            guards: None,
+            leading_guard_intervals: Vec::new(),
+            trailing_guard_intervals: Vec::new(),
+            detector: Detector::new(config.detector_settings()),
+            analysis_time: Duration::ZERO,
+            throttle_time: Duration::ZERO,
+        }
+    }
+
+    // A window with no samples, e.g. after a radio error, counts as inactive
+    pub fn skip_window(&mut self) {
+        self.window_states.push(false);
+        if self.config.debug_sample_counts {
+            self.sample_counts.push(0);
+        }
+        if self.config.debug_raw_max {
+            self.raw_max_per_window.push(None);
+        }
+    }
+
+    pub fn set_radio_config(&mut self, radio_config: RadioConfig) {
+        self.radio_config = Some(radio_config);
+    }
+
+    /// Treat windows starting before `main_start` or from `main_end` on as guard windows,
+    /// reported separately from the main results.
+    pub fn set_guards(&mut self, main_start: Duration, main_end: Duration) {
+        self.guards = Some((main_start, main_end));
+    }
+
+    /// Process one window of raw samples spanning `window_start` to `window_end` from the scan start.
+    pub fn process_window(&mut self, window_start: Duration, window_end: Duration, raw_samples: Vec<u8>) {
+        let started = Instant::now();
+        self.analyze_window(window_start, window_end, raw_samples);
+        let busy = started.elapsed();
+        self.analysis_time += busy;
+
+        // Checked to be in (0, 1] when the config was loaded, so what the summary reports is what applies
+        if let Some(duty_cycle) = self.config.analysis_duty_cycle {
+            let pause = busy.mul_f64(1.0 / duty_cycle - 1.0);
+            self.throttle_time += pause;
+            std::thread::sleep(pause);
+        }
+    }
+
+    fn analyze_window(&mut self, window_start: Duration, window_end: Duration, raw_samples: Vec<u8>) {
+        let config = self.config;
+        let step_label = step_script::active_label(&config.step_script, config.step_script_end, window_start.as_secs());
+
+        if config.debug_sample_counts {
+            self.sample_counts.push(raw_samples.len() as u64 / source::BYTES_PER_SAMPLE);
+        }
+        if config.debug_raw_max {
+            self.raw_max_per_window.push(raw_samples.iter().max().copied());
+        }
+        let detection = self.detector.process(&raw_samples);
+        if detection.idle {
+            self.idle_windows += 1;
+            self.window_states.push(false);
+            return;
+        }
+
+        if !self.clipping_reported && is_clipped(&raw_samples) {
+            warn_clipped(config, self.gains);
+            self.clipping_reported = true;
+        }
+        if config.needs_noise_floor() {
+            for (total, count) in self.histogram.iter_mut().zip(byte_histogram(&raw_samples)) {
+                *total += count;
+            }
+        }
+        let mut window_active = false;
+
+        if let Some(strength) = detection.strength {
+            self.peak_strength = Some(self.peak_strength.map_or(strength, |peak| peak.max(strength)));
+            if config.threshold_sweep.is_some() {
+                self.window_strengths.push(strength);
+            }
+            let guard_intervals = match self.guards {
+                Some((main_start, _)) if window_start < main_start => Some(&mut self.leading_guard_intervals),
+                Some((_, main_end)) if window_start >= main_end => Some(&mut self.trailing_guard_intervals),
+                _ => None,
+            };
+            if detection.detected {
+                window_active = true;
+                let elapsed = window_end.as_secs();
+                if let Some(guard_intervals) = guard_intervals {
+                    guard_intervals.push((elapsed, elapsed + 1));
+                } else {
+                    self.signal_detected = true;
+                    self.first_detection.get_or_insert(elapsed as f64);
+                    self.max_strength = self.max_strength.max(strength);
+                    self.intervals.push((elapsed, elapsed + 1));
+                    if let Some(syslog) = self.syslog.as_mut() {
+                        let step = step_label.map(|label| format!(" step=\"{}\"", label)).unwrap_or_default();
+                        syslog.send(&format!("Z-Wave signal detected at={}s strength={:.1}{}", elapsed, strength, step));
+                    }
+                    if let Some(on_detect) = self.on_detect.as_ref() {
+                        on_detect.detected(self.frequency, strength);
+                    }
+                }
+            }
+            if let Some(label) = step_label {
+                self.step_breakdown.record(label, strength, window_active);
+            }
+        }
+        self.window_states.push(window_active);
+    }
+
+    /// Build the result and report it to syslog, stdout and the result file as configured.
+    pub fn finish(mut self, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let config = self.config;
+
+        // Each transmission starts where a run of consecutive detection windows begins
+        let transmission_starts: Vec<u64> = merge_intervals(self.intervals.clone(), 0).iter().map(|interval| interval.0).collect();
+        let period = if config.estimate_period {
+            estimate_period(&transmission_starts, config.min_detections_for_period)
+        } else {
+            None
+        };
+
+        // Detections within 5 seconds of each other are reported as one interval
+        let merged_intervals = merge_intervals(self.intervals, 5);
+        let (durations_str, durations_truncated) = match config.max_duration_intervals {
+            Some(cap) => format_intervals_capped(&merged_intervals, cap),
+            None => (format_intervals(&merged_intervals), false),
+        };
+
+        let noise_floor_ci = noise_floor_interval(&self.histogram, NOISE_FLOOR_CI_Z).filter(|_| config.emit_noise_floor_ci);
+        let result = SignalData {
+            frequency: self.frequency as f64 / 1_000_000.0,
+            is_signal_detected: self.signal_detected,
+            max_signal_strength: self.max_strength,
+            raw_strength_dbfs: self.peak_strength.map(strength_dbfs),
+            calibrated_dbm: self.peak_strength.zip(config.calibration_offset_db).map(|(peak, offset)| strength_dbfs(peak) + offset),
+            min_detectable_dbm: min_detectable_dbm(config, config.rise_detection.as_ref(), estimate_noise_floor(&self.histogram)),
+            zwave_durations: durations_str,
+            dynamic_range_db: if config.emit_dynamic_range {
+                self.peak_strength.zip(estimate_noise_floor(&self.histogram)).map(|(peak, floor)| peak - floor)
+            } else {
+                None
+            },
+            noise_floor_db: estimate_noise_floor(&self.histogram).filter(|_| config.emit_noise_floor_ci),
+            noise_floor_ci_low: noise_floor_ci.map(|(low, _)| low),
+            noise_floor_ci_high: noise_floor_ci.map(|(_, high)| high),
+            raw_max_sample: self.raw_max_per_window.iter().flatten().max().copied().filter(|_| config.debug_raw_max),
+            raw_max_per_window: Some(self.raw_max_per_window).filter(|_| config.debug_raw_max),
+            sample_counts: Some(self.sample_counts).filter(|_| config.debug_sample_counts),
+            // Windows are one second long
+            expected_sample_count: config.debug_sample_counts.then_some(self.sample_rate),
+            timeline: config.emit_timeline.then(|| {
+                run_lengths(&self.window_states).into_iter()
+                    .map(|(active, duration)| TimelineRun { active, duration })
+                    .collect()
+            }),
+            durations_truncated: config.max_duration_intervals.map(|_| durations_truncated),
+            intervals: config.max_duration_intervals.map(|_| merged_intervals),
+            estimated_period_secs: period.map(|(period, _)| period),
+            period_confidence: period.map(|(_, confidence)| confidence),
+            interrupted: shutdown::requested().then_some(true),
+            step_breakdown: (!config.step_script.is_empty()).then(|| self.step_breakdown.summaries()),
+            first_detection_secs: config.emit_first_detection.then_some(self.first_detection),
+            high_gain_with_amp: high_gain_with_amp(config, self.gains.lna).then_some(true),
+            receive_safe: config.receive_safe.then_some(true),
+            scan_id: scan_id::current(),
+            radio_config: self.radio_config,
+            context: read_context(config),
+            throttle: config.analysis_duty_cycle.map(|duty_cycle| ThrottleSummary {
+                analysis_duty_cycle: duty_cycle,
+                analysis_secs: self.analysis_time.as_secs_f64(),
+                throttle_secs: self.throttle_time.as_secs_f64(),
+            }),
+            threshold_sweep: config.threshold_sweep.as_ref().map(|sweep| threshold_counts(sweep, &self.window_strengths)),
+            rise: self.detector.baseline().filter(|_| config.rise_detection.is_some()).map(|baseline| RiseSummary {
+                baseline_db: baseline,
+                max_rise_db: self.detector.max_rise(),
+            }),
+            guard: self.guards.map(|(main_start, main_end)| GuardSummary {
+                leading_secs: main_start.as_secs(),
+                trailing_secs: config.trailing_guard_secs,
+                main_window: (main_start.as_secs(), main_end.as_secs()),
+                leading_durations: format_intervals(&merge_intervals(self.leading_guard_intervals, 5)),
+                trailing_durations: format_intervals(&merge_intervals(self.trailing_guard_intervals, 5)),
+            }),
+            energy_gate: config.energy_gate_db.map(|_| EnergyGateSummary::new(self.window_states.len(), self.idle_windows)),
+        };
+
+        if let Some(presence_config) = config.presence_score.as_ref() {
+            let active_windows = self.window_states.iter().filter(|&&active| active).count();
+            let duty_cycle = active_windows as f64 / self.window_states.len().max(1) as f64;
+            let peak_snr = self.peak_strength.zip(estimate_noise_floor(&self.histogram)).map_or(0.0, |(peak, floor)| peak - floor);
+            let score = presence::score(presence_config, duty_cycle, peak_snr);
+            scan_println!("Presence score: {:.0}", score);
+            presence::write_score(config, score)?;
+        }
+
+        if let Some(gate) = result.energy_gate.as_ref() {
+            scan_println!("Energy gate skipped analysis of {} of {} windows ({:.0}%)", gate.idle_windows, gate.windows, gate.idle_fraction * 100.0);
+        }
+
+        let json = output_format::render(&result, config.output_format, true)?;
+
+        if let Some(syslog) = self.syslog.as_mut() {
+            syslog.send(&summary_message(&result));
+        }
+
+        if !config.syslog.as_ref().is_some_and(|syslog| syslog.exclusive) {
+            println!("{}", json);
+            encryption::write_artifact(config.encryption.as_ref(), output_path, json.as_bytes())?;
+        }
+
+        if let Some(path) = config.fifo_path.as_ref() {
+            fifo_output::send(path, &serde_json::to_string(&result)?);
+        }
+
+        if let Some((history, floor)) = config.noise_history.as_ref().zip(estimate_noise_floor(&self.histogram)) {
+            history::record(history, config.encryption.as_ref(), self.frequency, floor)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The sweep was checked when the config was loaded, see ThresholdSweepConfig::check
+fn threshold_counts(sweep: &ThresholdSweepConfig, window_strengths: &[f64]) -> Vec<ThresholdCount> {
+    (0..=sweep.steps() as i64)
+        .map(|index| {
+            let threshold = sweep.start + index as f64 * sweep.step;
+            ThresholdCount {
+                threshold,
+                active_windows: window_strengths.iter().filter(|&&strength| strength > threshold).count(),
+            }
+        })
+        .collect()
+}
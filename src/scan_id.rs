@@ -0,0 +1,33 @@
+// Optional per-scan UUID, used to correlate a result with its log lines and artifact files
+// across machines. While a scan is running its ID prefixes scan log lines (see scan_println!),
+// syslog messages and artifact file names.
+
+use std::sync::Mutex;
+
+static CURRENT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Start a new scan, generating a fresh ID if `enabled`, and return it.
+pub fn start(enabled: bool) -> Option<String> {
+    let id = enabled.then(|| uuid::Uuid::new_v4().to_string());
+    *CURRENT.lock().unwrap() = id.clone();
+    id
+}
+
+pub fn current() -> Option<String> {
+    CURRENT.lock().unwrap().clone()
+}
+
+pub fn log_prefix() -> String {
+    current().map(|id| format!("[{}] ", id)).unwrap_or_default()
+}
+
+/// Insert the current scan ID before the file extension, e.g. `zwave_scheduledata_<id>.json`.
+pub fn artifact_path(path: &str) -> String {
+    match current() {
+        Some(id) => match path.rsplit_once('.') {
+            Some((stem, extension)) => format!("{}_{}.{}", stem, id, extension),
+            None => format!("{}_{}", path, id),
+        },
+        None => path.to_string(),
+    }
+}
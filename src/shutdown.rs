@@ -0,0 +1,47 @@
+// Graceful shutdown on SIGINT or SIGTERM.
+//
+// The first signal sets a flag that scan loops check between windows, so they stop early and
+// still write their (partial) results. A second signal, or shutdown taking longer than the grace
+// period, exits the process immediately.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Exit status for a forced exit, as for a process killed by SIGINT
+const FORCED_EXIT_CODE: i32 = 130;
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+pub fn install(grace_period: Duration) {
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        REQUESTED.store(true, Ordering::SeqCst);
+        println!("Shutdown requested, finishing the current window (signal again to exit immediately)");
+
+        tokio::select! {
+            _ = wait_for_signal() => eprintln!("Second shutdown signal received, exiting immediately"),
+            _ = tokio::time::sleep(grace_period) => eprintln!("Shutdown took longer than {} seconds, exiting", grace_period.as_secs()),
+        }
+        std::process::exit(FORCED_EXIT_CODE);
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to register SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c().await.expect("Failed to register Ctrl-C handler");
+}
@@ -0,0 +1,83 @@
+use crate::shutdown;
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+// HackRF samples are interleaved 8-bit I/Q pairs
+pub const BYTES_PER_SAMPLE: u64 = 2;
+
+// How often a source waiting for input checks for a shutdown request
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+/// A source of raw HackRF sample bytes delivered a window at a time.
+pub trait SampleSource {
+    /// Next window of samples. The last window of a stream may be shorter than the rest;
+    /// None means the stream has ended.
+    fn next_window(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// Samples piped in on stdin, e.g. from `hackrf_transfer -r -`.
+///
+/// Stdin is read on its own thread so a quiet stream doesn't hold up shutdown: once shutdown is
+/// requested the stream is treated as ended, whether or not a read is still waiting for input.
+pub struct StdinSource {
+    windows: Receiver<io::Result<Vec<u8>>>,
+}
+
+impl StdinSource {
+    pub fn new(window_bytes: usize) -> Self {
+        // Holding one window keeps the pipe's backpressure on the sender
+        let (sender, windows) = mpsc::sync_channel(1);
+        std::thread::spawn(move || {
+            let mut stdin = io::stdin().lock();
+            loop {
+                let window = match read_window(&mut stdin, window_bytes) {
+                    Ok(Some(window)) => Ok(window),
+                    Ok(None) => return,
+                    Err(e) => Err(e),
+                };
+                let failed = window.is_err();
+                if sender.send(window).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        StdinSource { windows }
+    }
+}
+
+impl SampleSource for StdinSource {
+    fn next_window(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if shutdown::requested() {
+                return Ok(None);
+            }
+            match self.windows.recv_timeout(SHUTDOWN_POLL) {
+                Ok(window) => return window.map(Some),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            }
+        }
+    }
+}
+
+fn read_window(input: &mut impl Read, window_bytes: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut window = vec![0u8; window_bytes];
+    let mut filled = 0;
+
+    // A pipe can return fewer bytes than asked for, so keep reading until the window is full or EOF
+    while filled < window.len() {
+        match input.read(&mut window[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    if filled == 0 {
+        return Ok(None);
+    }
+    window.truncate(filled);
+    Ok(Some(window))
+}
@@ -0,0 +1,89 @@
+// Labelled steps for scripted experiments (e.g. stepping an external attenuator). Each scan
+// window is tagged with the step active when it started, and results are broken down per label.
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptStep {
+    pub duration_secs: u64,
+    pub label: String,
+}
+
+// What happens when the scan outlasts the script
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptEnd {
+    // Start again from the first step
+    #[default]
+    Repeat,
+    // Stay on the last step until the scan ends
+    Hold,
+}
+
+/// Label of the step active `elapsed_secs` into the scan, or None for an empty script.
+pub fn active_label(script: &[ScriptStep], end: ScriptEnd, elapsed_secs: u64) -> Option<&str> {
+    let total: u64 = script.iter().map(|step| step.duration_secs).sum();
+    if total == 0 {
+        return script.last().map(|step| step.label.as_str());
+    }
+
+    let offset = match end {
+        ScriptEnd::Repeat => elapsed_secs % total,
+        ScriptEnd::Hold if elapsed_secs >= total => return script.last().map(|step| step.label.as_str()),
+        ScriptEnd::Hold => elapsed_secs,
+    };
+
+    let mut step_end = 0;
+    for step in script {
+        step_end += step.duration_secs;
+        if offset < step_end {
+            return Some(step.label.as_str());
+        }
+    }
+
+    script.last().map(|step| step.label.as_str())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StepSummary {
+    pub label: String,
+    pub windows: usize,
+    pub detections: usize,
+    pub peak_strength: f64,
+    pub mean_strength: f64,
+}
+
+/// Per-label window statistics, in the order labels were first seen.
+#[derive(Default)]
+pub struct StepBreakdown {
+    steps: Vec<(String, Vec<f64>, usize)>,
+}
+
+impl StepBreakdown {
+    pub fn record(&mut self, label: &str, strength: f64, detected: bool) {
+        let index = match self.steps.iter().position(|(step_label, _, _)| step_label == label) {
+            Some(index) => index,
+            None => {
+                self.steps.push((label.to_string(), Vec::new(), 0));
+                self.steps.len() - 1
+            }
+        };
+        let (_, strengths, detections) = &mut self.steps[index];
+        strengths.push(strength);
+        if detected {
+            *detections += 1;
+        }
+    }
+
+    pub fn summaries(&self) -> Vec<StepSummary> {
+        self.steps.iter()
+            .map(|(label, strengths, detections)| StepSummary {
+                label: label.clone(),
+                windows: strengths.len(),
+                detections: *detections,
+                peak_strength: strengths.iter().copied().fold(0.0_f64, f64::max),
+                mean_strength: strengths.iter().sum::<f64>() / strengths.len().max(1) as f64,
+            })
+            .collect()
+    }
+}
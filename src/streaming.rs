@@ -0,0 +1,168 @@
+//! Incremental analysis for library users with their own sample source and buffering.
+//!
+//! Samples are pushed in whatever sizes the caller reads them, and a result is handed to the
+//! callback each time `chunk_samples` samples have been seen. As in [`crate::analysis`], each raw
+//! byte counts as one sample.
+
+use crate::analysis::{byte_histogram, estimate_noise_floor};
+use crate::detector::{Detector, DetectorSettings};
+
+/// Analysis of one chunk of samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisResult {
+    /// Position of the chunk's first sample in the stream.
+    pub start_sample: u64,
+    pub samples: u64,
+    /// Whether the energy gate skipped the chunk, leaving the fields below empty.
+    pub idle: bool,
+    pub max_strength_db: Option<f64>,
+    pub noise_floor_db: Option<f64>,
+    pub detected: bool,
+}
+
+pub struct StreamingAnalyzer<F: FnMut(&AnalysisResult)> {
+    chunk_samples: u64,
+    detector: Detector,
+    callback: F,
+    start_sample: u64,
+    chunk: Vec<u8>,
+}
+
+impl<F: FnMut(&AnalysisResult)> StreamingAnalyzer<F> {
+    /// Call `callback` with a result for every `chunk_samples` samples pushed, each chunk
+    /// detected on with the same [`Detector`] the CLI uses for its windows.
+    pub fn new(chunk_samples: u64, settings: DetectorSettings, callback: F) -> Self {
+        StreamingAnalyzer {
+            chunk_samples: chunk_samples.max(1),
+            detector: Detector::new(settings),
+            callback,
+            start_sample: 0,
+            chunk: Vec::new(),
+        }
+    }
+
+    /// Add samples, emitting a result at each chunk boundary they cross.
+    pub fn push(&mut self, mut samples: &[u8]) {
+        while !samples.is_empty() {
+            let room = (self.chunk_samples - self.chunk.len() as u64).min(samples.len() as u64) as usize;
+            let (chunk, rest) = samples.split_at(room);
+            self.chunk.extend_from_slice(chunk);
+
+            if self.chunk.len() as u64 == self.chunk_samples {
+                self.emit();
+            }
+            samples = rest;
+        }
+    }
+
+    /// Emit a result for a partly filled chunk, e.g. at the end of the stream.
+    pub fn flush(&mut self) {
+        if !self.chunk.is_empty() {
+            self.emit();
+        }
+    }
+
+    fn emit(&mut self) {
+        let detection = self.detector.process(&self.chunk);
+        let noise_floor_db = if detection.idle { None } else { estimate_noise_floor(&byte_histogram(&self.chunk)) };
+        let result = AnalysisResult {
+            start_sample: self.start_sample,
+            samples: self.chunk.len() as u64,
+            idle: detection.idle,
+            max_strength_db: detection.strength,
+            noise_floor_db,
+            detected: detection.detected,
+        };
+        (self.callback)(&result);
+
+        self.start_sample += result.samples;
+        self.chunk.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::sample_db;
+    use crate::detector::RiseDetection;
+
+    fn analyze(chunk_samples: u64, pushes: &[&[u8]], flush: bool) -> Vec<AnalysisResult> {
+        analyze_with(DetectorSettings { detection_threshold: 40.0, ..Default::default() }, chunk_samples, pushes, flush)
+    }
+
+    fn analyze_with(settings: DetectorSettings, chunk_samples: u64, pushes: &[&[u8]], flush: bool) -> Vec<AnalysisResult> {
+        let mut results = Vec::new();
+        {
+            let mut analyzer = StreamingAnalyzer::new(chunk_samples, settings, |result: &AnalysisResult| results.push(result.clone()));
+            for samples in pushes {
+                analyzer.push(samples);
+            }
+            if flush {
+                analyzer.flush();
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn chunk_split_across_pushes_emits_once() {
+        let results = analyze(10, &[&[1; 3], &[2; 4], &[3; 3], &[4; 2]], false);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].start_sample, 0);
+        assert_eq!(results[0].samples, 10);
+        assert_eq!(results[0].max_strength_db, Some(sample_db(3)));
+    }
+
+    #[test]
+    fn push_spanning_chunks_emits_each() {
+        let results = analyze(4, &[&[1; 3], &[2; 7]], false);
+        let positions: Vec<(u64, u64)> = results.iter().map(|result| (result.start_sample, result.samples)).collect();
+        assert_eq!(positions, vec![(0, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn flush_emits_partial_chunk() {
+        let results = analyze(10, &[&[5; 13]], true);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].start_sample, 10);
+        assert_eq!(results[1].samples, 3);
+    }
+
+    #[test]
+    fn flush_without_samples_emits_nothing() {
+        assert!(analyze(10, &[], true).is_empty());
+        assert_eq!(analyze(5, &[&[1; 5]], true).len(), 1);
+    }
+
+    #[test]
+    fn state_resets_between_chunks() {
+        let results = analyze(4, &[&[255; 4], &[2; 4]], false);
+        assert!(results[0].detected);
+        assert!(!results[1].detected);
+        assert_eq!(results[1].max_strength_db, Some(sample_db(2)));
+        assert_eq!(results[1].noise_floor_db, estimate_noise_floor(&byte_histogram(&[2; 4])));
+    }
+
+    #[test]
+    fn energy_gate_marks_quiet_chunks_idle() {
+        let settings = DetectorSettings { energy_gate_db: Some(20.0), energy_gate_decimation: 1, ..Default::default() };
+        let results = analyze_with(settings, 4, &[&[1; 4], &[200; 4]], false);
+        assert!(results[0].idle);
+        assert_eq!(results[0].max_strength_db, None);
+        assert_eq!(results[0].noise_floor_db, None);
+        assert!(!results[1].idle);
+        assert!(results[1].detected);
+    }
+
+    #[test]
+    fn rise_baseline_carries_across_chunks() {
+        let settings = DetectorSettings {
+            detection_threshold: 1000.0,
+            rise_detection: Some(RiseDetection { delta_db: 6.0, baseline_alpha: 0.1 }),
+            ..Default::default()
+        };
+        let results = analyze_with(settings, 4, &[&[10; 4], &[10; 4], &[100; 4]], false);
+        let detected: Vec<bool> = results.iter().map(|result| result.detected).collect();
+        assert_eq!(detected, vec![false, false, true]);
+    }
+}
@@ -0,0 +1,200 @@
+// One-shot site survey for a new installation: device healthcheck, noise calibration, a frequency
+// sweep and a detection scan, run in sequence with all artifacts bundled into one directory.
+//
+// A failed phase is recorded in the report and the survey moves on; later phases fall back to the
+// base config where they would have used an earlier phase's result.
+
+use crate::{byte_histogram, capture, compare, encryption, estimate_noise_floor, is_clipped, run_scan_over_duration, scan_id, shutdown, take_or_open, version_string, Config, Gains, RadioSlot};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Swept when compare_frequencies is empty: the EU Z-Wave channels
+const DEFAULT_SWEEP_FREQUENCIES: [u64; 2] = [868_400_000, 869_850_000];
+
+#[derive(Serialize)]
+struct Healthcheck {
+    tool_version: String,
+    board_id: u8,
+    firmware_version: String,
+    usb_device_version: String,
+}
+
+#[derive(Serialize)]
+struct NoiseCalibration {
+    frequency: u64,
+    capture_secs: u64,
+    samples: usize,
+    noise_floor_db: Option<f64>,
+    clipped: bool,
+}
+
+enum Outcome {
+    Ok(String),
+    Skipped,
+    Failed(String),
+}
+
+struct Phase {
+    name: &'static str,
+    outcome: Outcome,
+}
+
+/// zwave_module survey [--skip-healthcheck] [--skip-noise] [--skip-sweep] [--skip-scan]
+pub async fn run_survey(config: &Config, gains: Gains, radio: &mut RadioSlot, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let skipped = |phase: &str| args.iter().any(|arg| *arg == format!("--skip-{}", phase));
+
+    scan_id::start(config.scan_uuid);
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let bundle = scan_id::artifact_path(&format!("survey_{}", started));
+    std::fs::create_dir_all(&bundle)?;
+    let bundle = Path::new(&bundle);
+    scan_println!("Running site survey, writing artifacts to {}", bundle.display());
+
+    let mut phases = Vec::new();
+    let mut recommended_frequency = None;
+
+    for name in ["healthcheck", "noise", "sweep", "scan"] {
+        if skipped(name) || shutdown::requested() {
+            phases.push(Phase { name, outcome: Outcome::Skipped });
+            continue;
+        }
+        scan_println!("Survey phase: {}", name);
+
+        let result = match name {
+            "healthcheck" => healthcheck(config, radio, bundle),
+            "noise" => noise(config, gains, radio, bundle),
+            "sweep" => sweep(config, gains, radio, bundle).map(|frequency| {
+                recommended_frequency = frequency;
+                match frequency {
+                    Some(frequency) => format!("recommended {:.3} MHz", frequency as f64 / 1_000_000.0),
+                    None => String::from("no channel captured"),
+                }
+            }),
+            _ => scan(config, gains, radio, bundle, recommended_frequency).await,
+        };
+
+        let outcome = match result {
+            Ok(detail) => Outcome::Ok(detail),
+            Err(e) => {
+                scan_println!("Survey phase {} failed: {}", name, e);
+                Outcome::Failed(e.to_string())
+            }
+        };
+        phases.push(Phase { name, outcome });
+    }
+
+    let recommended = recommended_config(config, recommended_frequency);
+    write_json(config, bundle, "recommended_config.json", &recommended)?;
+
+    let report = report(started, &phases, &recommended);
+    let report_path = bundle.join("report.txt");
+    encryption::write_artifact(config.encryption.as_ref(), &report_path.to_string_lossy(), report.as_bytes())?;
+
+    println!();
+    print!("{}", report);
+    println!("Survey bundle: {}", bundle.display());
+
+    Ok(())
+}
+
+fn healthcheck(config: &Config, radio: &mut RadioSlot, bundle: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let device = take_or_open(radio)?;
+    let fingerprint = (|| -> Result<Healthcheck, hackrfone::Error> {
+        Ok(Healthcheck {
+            tool_version: version_string(),
+            board_id: device.board_id()?,
+            firmware_version: device.version()?,
+            usb_device_version: device.device_version().to_string(),
+        })
+    })();
+    radio.device = Some(device);
+    let fingerprint = fingerprint?;
+
+    write_json(config, bundle, "healthcheck.json", &fingerprint)?;
+    Ok(format!("board {} firmware {}", fingerprint.board_id, fingerprint.firmware_version))
+}
+
+fn noise(config: &Config, gains: Gains, radio: &mut RadioSlot, bundle: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let duration = Duration::from_secs(config.instant_duration);
+    let raw_samples = capture(config, radio, config.frequency, config.sample_rate, gains, duration)?
+        .ok_or("capture skipped after radio error")?;
+
+    let calibration = NoiseCalibration {
+        frequency: config.frequency,
+        capture_secs: config.instant_duration,
+        samples: raw_samples.len(),
+        noise_floor_db: estimate_noise_floor(&byte_histogram(&raw_samples)),
+        clipped: is_clipped(&raw_samples),
+    };
+    write_json(config, bundle, "noise.json", &calibration)?;
+
+    let floor = calibration.noise_floor_db.map_or(String::from("unknown"), |floor| format!("{:.1} dB", floor));
+    Ok(format!("noise floor {}{}", floor, if calibration.clipped { ", clipped" } else { "" }))
+}
+
+fn sweep(config: &Config, gains: Gains, radio: &mut RadioSlot, bundle: &Path) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+    let frequencies = if config.compare_frequencies.is_empty() {
+        &DEFAULT_SWEEP_FREQUENCIES[..]
+    } else {
+        &config.compare_frequencies[..]
+    };
+    let result = compare::compare_frequencies(config, gains, radio, frequencies, config.compare_rounds)?;
+    write_json(config, bundle, "sweep.json", &result)?;
+    Ok(result.recommended_frequency)
+}
+
+async fn scan(config: &Config, gains: Gains, radio: &mut RadioSlot, bundle: &Path, frequency: Option<u64>) -> Result<String, Box<dyn std::error::Error>> {
+    let mut scan_config = config.clone();
+    scan_config.frequency = frequency.unwrap_or(config.frequency);
+    scan_config.scan_duration = config.survey_scan_secs;
+    scan_config.start_after_duration = 0;
+
+    let output_path = bundle.join(format!("scan.{}", config.output_format.extension()));
+    run_scan_over_duration(&scan_config, gains, radio, &output_path.to_string_lossy()).await?;
+    Ok(format!("{} seconds on {:.3} MHz", scan_config.scan_duration, scan_config.frequency as f64 / 1_000_000.0))
+}
+
+// The base config with the survey's findings applied, as a scheduled scan
+fn recommended_config(config: &Config, frequency: Option<u64>) -> Config {
+    let mut recommended = config.clone();
+    recommended.frequency = frequency.unwrap_or(config.frequency);
+    recommended.instant_scan = false;
+    recommended
+}
+
+fn report(started: u64, phases: &[Phase], recommended: &Config) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "Z-Wave site survey ({})", version_string());
+    let _ = writeln!(report, "Started: {} (unix seconds)", started);
+    if let Some(id) = scan_id::current() {
+        let _ = writeln!(report, "Scan ID: {}", id);
+    }
+    let _ = writeln!(report);
+
+    for phase in phases {
+        let status = match &phase.outcome {
+            Outcome::Ok(detail) => format!("ok, {}", detail),
+            Outcome::Skipped => String::from("skipped"),
+            Outcome::Failed(error) => format!("FAILED, {}", error),
+        };
+        let _ = writeln!(report, "{:<12} {}", phase.name, status);
+    }
+
+    let _ = writeln!(report);
+    let _ = writeln!(
+        report,
+        "Recommended config: {:.3} MHz, LNA {} dB, VGA {} dB, threshold {:.1} (recommended_config.json)",
+        recommended.frequency as f64 / 1_000_000.0,
+        recommended.lna_gain,
+        recommended.vga_gain,
+        recommended.detection_threshold,
+    );
+    report
+}
+
+fn write_json<T: Serialize>(config: &Config, bundle: &Path, name: &str, value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(value)?;
+    encryption::write_artifact(config.encryption.as_ref(), &bundle.join(name).to_string_lossy(), json.as_bytes())
+}
@@ -0,0 +1,85 @@
+use serde::{Serialize, Deserialize};
+use syslog::{Facility, Formatter3164, Logger, LoggerBackend};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyslogConfig {
+    #[serde(default = "default_facility")]
+    pub facility: String,
+    #[serde(default)]
+    pub severity: Severity,
+    // Send results only to syslog, skipping the result file and JSON on stdout
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+fn default_facility() -> String {
+    String::from("daemon")
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Emerg,
+    Alert,
+    Crit,
+    Err,
+    Warning,
+    Notice,
+    #[default]
+    Info,
+    Debug,
+}
+
+pub struct SyslogOutput {
+    logger: Option<Logger<LoggerBackend, Formatter3164>>,
+    severity: Severity,
+}
+
+impl SyslogOutput {
+    /// Connect to the local syslog daemon. If that fails, messages go to stderr instead.
+    pub fn connect(config: &SyslogConfig) -> Self {
+        let facility = config.facility.parse().unwrap_or_else(|_| {
+            eprintln!("Unknown syslog facility \"{}\", using daemon", config.facility);
+            Facility::LOG_DAEMON
+        });
+        let formatter = Formatter3164 {
+            facility,
+            hostname: None,
+            process: String::from(env!("CARGO_PKG_NAME")),
+            pid: std::process::id(),
+        };
+
+        let logger = match syslog::unix(formatter) {
+            Ok(logger) => Some(logger),
+            Err(e) => {
+                eprintln!("Failed to connect to syslog ({}), logging to stderr", e);
+                None
+            }
+        };
+
+        SyslogOutput { logger, severity: config.severity }
+    }
+
+    pub fn send(&mut self, message: &str) {
+        let message = &format!("{}{}", crate::scan_id::log_prefix(), message);
+        let Some(logger) = self.logger.as_mut() else {
+            eprintln!("{}", message);
+            return;
+        };
+
+        let sent = match self.severity {
+            Severity::Emerg => logger.emerg(message),
+            Severity::Alert => logger.alert(message),
+            Severity::Crit => logger.crit(message),
+            Severity::Err => logger.err(message),
+            Severity::Warning => logger.warning(message),
+            Severity::Notice => logger.notice(message),
+            Severity::Info => logger.info(message),
+            Severity::Debug => logger.debug(message),
+        };
+
+        if let Err(e) = sent {
+            eprintln!("Failed to write to syslog ({}): {}", e, message);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+// Interactive terminal UI for bench work: captures short windows back to back like placement mode
+// and shows the latest levels, a strength history, the sample level distribution, a rolling
+// detection log and the radio status. Frequency and gains can be changed with the keyboard.
+//
+// Keys: left/right frequency, l/L LNA gain, v/V VGA gain, a amp, q or Esc quit.
+//
+// Nothing may be printed while the UI is up, so radio errors go to the detection log, and the
+// loop ends once scan_budget_bytes is spent.
+
+use crate::{analyze_samples, budget, byte_histogram, capture_logged, check_amp_with_lna, estimate_noise_floor, shutdown, Config, Gains, RadioSlot};
+use crate::{LNA_GAIN_MAX, LNA_GAIN_STEP, VGA_GAIN_MAX, VGA_GAIN_STEP};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{BarChart, Block, List, Paragraph, Sparkline};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const FREQUENCY_STEP: u64 = 100_000;
+const HISTORY_LEN: usize = 200;
+const LOG_LEN: usize = 50;
+// Level distribution bars, each covering 256 / LEVEL_BINS raw sample values
+const LEVEL_BINS: usize = 16;
+
+struct State {
+    frequency: u64,
+    gains: Gains,
+    strength: f64,
+    noise_floor: f64,
+    samples: usize,
+    levels: [u64; LEVEL_BINS],
+    history: VecDeque<u64>,
+    log: VecDeque<String>,
+    status: String,
+}
+
+impl State {
+    fn log(&mut self, message: String) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        self.log.push_front(format!("{} {}", timestamp, message));
+        self.log.truncate(LOG_LEN);
+    }
+}
+
+pub fn run_tui(config: &Config, gains: Gains, radio: &mut RadioSlot) -> Result<(), Box<dyn std::error::Error>> {
+    let mut terminal = ratatui::init();
+    let result = run_loop(&mut terminal, config, gains, radio);
+    ratatui::restore();
+    if let Some(budget) = budget::limit().filter(|_| budget::exhausted()) {
+        println!("Scan budget of {} bytes spent, stopped", budget);
+    }
+    result
+}
+
+fn run_loop(terminal: &mut ratatui::DefaultTerminal, config: &Config, gains: Gains, radio: &mut RadioSlot) -> Result<(), Box<dyn std::error::Error>> {
+    let capture_duration = Duration::from_millis(config.placement_capture_ms);
+    let mut state = State {
+        frequency: config.frequency,
+        gains,
+        strength: 0.0,
+        noise_floor: 0.0,
+        samples: 0,
+        levels: [0; LEVEL_BINS],
+        history: VecDeque::with_capacity(HISTORY_LEN),
+        log: VecDeque::with_capacity(LOG_LEN),
+        status: String::from("starting"),
+    };
+    let mut detecting = false;
+
+    while !shutdown::requested() && !budget::exhausted() {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Right => state.frequency += FREQUENCY_STEP,
+                KeyCode::Left => state.frequency = state.frequency.saturating_sub(FREQUENCY_STEP),
+                KeyCode::Char('L') => change_lna(config, &mut state, LNA_GAIN_STEP),
+                KeyCode::Char('l') => change_lna(config, &mut state, -LNA_GAIN_STEP),
+                KeyCode::Char('V') => state.gains.vga = step_gain(state.gains.vga, VGA_GAIN_STEP, VGA_GAIN_MAX),
+                KeyCode::Char('v') => state.gains.vga = step_gain(state.gains.vga, -VGA_GAIN_STEP, VGA_GAIN_MAX),
+                KeyCode::Char('a') => toggle_amp(config, &mut state),
+                _ => {}
+            }
+        }
+
+        let started = Instant::now();
+        let mut radio_log = Vec::new();
+        let captured = capture_logged(config, radio, state.frequency, config.sample_rate, state.gains, capture_duration, &mut |message| radio_log.push(message));
+        for message in radio_log {
+            state.log(message);
+        }
+        match captured {
+            Ok(Some(raw_samples)) => {
+                let histogram = byte_histogram(&raw_samples);
+                state.noise_floor = estimate_noise_floor(&histogram).unwrap_or(0.0);
+                for (bin, counts) in state.levels.iter_mut().zip(histogram.chunks(256 / LEVEL_BINS)) {
+                    *bin = counts.iter().sum();
+                }
+                state.samples = raw_samples.len();
+                state.strength = analyze_samples(raw_samples).into_iter().fold(0.0_f64, f64::max);
+                state.status = format!("ok, {} samples in {} ms", state.samples, started.elapsed().as_millis());
+
+                let detected = state.strength > config.detection_threshold;
+                if detected && !detecting {
+                    state.log(format!("detected {:.3} MHz strength {:.1} dB", state.frequency as f64 / 1_000_000.0, state.strength));
+                }
+                detecting = detected;
+            }
+            Ok(None) => state.status = String::from("window skipped after radio error"),
+            Err(e) => state.status = format!("radio error: {}", e),
+        }
+        if state.history.len() == HISTORY_LEN {
+            state.history.pop_front();
+        }
+        state.history.push_back(state.strength.max(0.0) as u64);
+
+        terminal.draw(|frame| draw(frame, config, &state))?;
+    }
+
+    Ok(())
+}
+
+fn step_gain(gain: u16, step: i32, max: i32) -> u16 {
+    (gain as i32 + step).clamp(0, max) as u16
+}
+
+fn change_lna(config: &Config, state: &mut State, step: i32) {
+    let lna = step_gain(state.gains.lna, step, LNA_GAIN_MAX);
+    if state.gains.amp {
+        if let Err(e) = check_amp_with_lna(config, lna) {
+            state.log(format!("LNA not changed: {}", e));
+            return;
+        }
+    }
+    state.gains.lna = lna;
+}
+
+fn toggle_amp(config: &Config, state: &mut State) {
+    if config.receive_safe {
+        state.log(String::from("amp stays off in receive_safe mode"));
+        return;
+    }
+    if !state.gains.amp {
+        if let Err(e) = check_amp_with_lna(config, state.gains.lna) {
+            state.log(format!("amp not enabled: {}", e));
+            return;
+        }
+    }
+    state.gains.amp = !state.gains.amp;
+}
+
+fn draw(frame: &mut Frame, config: &Config, state: &State) {
+    let [readout, history, bottom] = Layout::vertical([Constraint::Length(4), Constraint::Length(8), Constraint::Fill(1)])
+        .areas(frame.area());
+    let [levels, log] = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(bottom);
+
+    let detected = state.strength > config.detection_threshold;
+    let readout_text = format!(
+        "{:.3} MHz   LNA {} dB   VGA {} dB   amp {}   antenna power {}\n\
+         strength {:.1} dB   noise floor {:.1} dB   SNR {:.1} dB   {}\n\
+         radio: {}",
+        state.frequency as f64 / 1_000_000.0,
+        state.gains.lna,
+        state.gains.vga,
+        if state.gains.amp { "on" } else { "off" },
+        if state.gains.antenna_power { "on" } else { "off" },
+        state.strength,
+        state.noise_floor,
+        state.strength - state.noise_floor,
+        if detected { "DETECTED" } else { "" },
+        state.status,
+    );
+    frame.render_widget(
+        Paragraph::new(readout_text).block(Block::bordered().title(" zwave_module  (←/→ freq, l/L lna, v/V vga, a amp, q quit) ")),
+        readout,
+    );
+
+    let history_data: Vec<u64> = state.history.iter().copied().collect();
+    frame.render_widget(
+        Sparkline::default()
+            .block(Block::bordered().title(" max strength "))
+            .data(&history_data)
+            .max(49)
+            .style(Style::default().fg(if detected { Color::Red } else { Color::Green })),
+        history,
+    );
+
+    let labels: Vec<String> = (0..LEVEL_BINS).map(|bin| (bin * 256 / LEVEL_BINS).to_string()).collect();
+    let bars: Vec<(&str, u64)> = labels.iter().map(String::as_str).zip(state.levels).collect();
+    frame.render_widget(
+        BarChart::default()
+            .block(Block::bordered().title(" sample levels "))
+            .data(bars.as_slice())
+            .bar_width(3)
+            .bar_gap(1),
+        levels,
+    );
+
+    frame.render_widget(
+        List::new(state.log.iter().map(String::as_str)).block(Block::bordered().title(" detections ")),
+        log,
+    );
+}
@@ -0,0 +1,52 @@
+// SIGTERM during a scan stops it and still writes the partial result, even while the input
+// stream is idle. Runs `analyze --stdin` so no radio is needed.
+
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// One-second windows at this rate are 2000 bytes of I/Q pairs
+const SAMPLE_RATE: usize = 1000;
+const WINDOW_BYTES: usize = SAMPLE_RATE * 2;
+
+#[test]
+fn sigterm_writes_partial_result() {
+    let dir = std::env::temp_dir().join(format!("zwave_module_shutdown_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_zwave_module"))
+        .args(["analyze", "--stdin", "--sample-rate", &SAMPLE_RATE.to_string(), "--frequency", "868400000"])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // Wait for the scan to start, and give the signal handlers a moment to be registered
+    let mut line = String::new();
+    while !line.contains("Analyzing samples") {
+        line.clear();
+        assert_ne!(stdout.read_line(&mut line).unwrap(), 0, "analyze exited before starting");
+    }
+    std::thread::sleep(Duration::from_millis(200));
+
+    stdin.write_all(&[1; 3 * WINDOW_BYTES]).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(unsafe { libc::kill(child.id() as i32, libc::SIGTERM) }, 0);
+
+    // Nothing more is written, so the scan has to stop while waiting on a quiet stream
+    let status = child.wait().unwrap();
+    drop(stdin);
+    assert!(status.success(), "analyze exited with {}", status);
+
+    let result = std::fs::read_to_string(dir.join("zwave_analyzedata.json")).unwrap();
+    let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+    assert_eq!(result["interrupted"], true);
+    assert_eq!(result["is_signal_detected"], false);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}